@@ -0,0 +1,490 @@
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+
+use crate::config::Config;
+
+pub(crate) const SAMPLE_RATE: u32 = 44100;
+
+pub(crate) const AUDIO_PATTERN_LEN: usize = 16;
+const AUDIO_PATTERN_BITS: f32 = (AUDIO_PATTERN_LEN * 8) as f32;
+pub(crate) const DEFAULT_PITCH: u8 = 64;
+
+// The XO-CHIP sound registers. Until a program loads a pattern, the plain buzzer tone is played.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct SoundRegisters {
+    pub(crate) pattern: Option<[u8; AUDIO_PATTERN_LEN]>,
+    pub(crate) pitch: u8,
+}
+
+impl SoundRegisters {
+    pub(crate) const fn new() -> Self {
+        Self { pattern: None, pitch: DEFAULT_PITCH }
+    }
+
+    // The rate, in bits per second, at which the pattern is played.
+    pub(crate) fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+// Emitted by the CPU whenever the sound timer is written. The tone plays for `duration`
+// frames from that moment, a duration of 0 stops it. `clock` is the number of instructions
+// executed in the frame before the write.
+#[derive(Clone, Copy)]
+pub(crate) struct SoundEvent {
+    pub(crate) clock: u32,
+    pub(crate) duration: u8,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Waveform {
+    Square,
+    // A square wave that is high for `pulse_width` of every period.
+    Pulse,
+    Triangle,
+    Sine,
+    Noise,
+}
+
+impl std::str::FromStr for Waveform {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "square" => Ok(Self::Square),
+            "pulse" => Ok(Self::Pulse),
+            "triangle" => Ok(Self::Triangle),
+            "sine" => Ok(Self::Sine),
+            "noise" => Ok(Self::Noise),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct BuzzerSettings {
+    pub(crate) frequency: f32,
+    pub(crate) waveform: Waveform,
+    pub(crate) pulse_width: f32,
+    pub(crate) volume: f32,
+    // Fading the tone in and out avoids the clicks of an instant start or stop.
+    pub(crate) attack_ms: f32,
+    pub(crate) release_ms: f32,
+}
+
+impl Default for BuzzerSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            pulse_width: 0.25,
+            volume: 0.25,
+            attack_ms: 2.0,
+            release_ms: 10.0,
+        }
+    }
+}
+
+impl BuzzerSettings {
+    // Reads the [audio] section of the config, keeping the defaults for anything missing.
+    pub(crate) fn from_config(config: &Config) -> Result<Self, String> {
+        let defaults = Self::default();
+
+        let settings = Self {
+            frequency: config.get_parsed("audio", "frequency")?.unwrap_or(defaults.frequency),
+            waveform: config.get_parsed("audio", "waveform")?.unwrap_or(defaults.waveform),
+            pulse_width: config.get_parsed("audio", "pulse_width")?.unwrap_or(defaults.pulse_width),
+            volume: config.get_parsed("audio", "volume")?.unwrap_or(defaults.volume),
+            attack_ms: config.get_parsed("audio", "attack_ms")?.unwrap_or(defaults.attack_ms),
+            release_ms: config.get_parsed("audio", "release_ms")?.unwrap_or(defaults.release_ms),
+        };
+
+        // Written so that NaN, which fails every comparison, is rejected too.
+        settings.check_frequency(SAMPLE_RATE as i32)?;
+        if !(0.0..=1.0).contains(&settings.volume) {
+            return Err("The buzzer volume must be between 0 and 1.".to_string());
+        }
+        if !(settings.pulse_width > 0.0 && settings.pulse_width < 1.0) {
+            return Err("The pulse width must be between 0 and 1.".to_string());
+        }
+        let is_valid_time = |ms: f32| ms >= 0.0 && ms.is_finite();
+        if !(is_valid_time(settings.attack_ms) && is_valid_time(settings.release_ms)) {
+            return Err("The attack and release times must be finite and can't be negative.".to_string());
+        }
+
+        Ok(settings)
+    }
+
+    // Above half the sample rate, a tone can't be played, only aliases of it. The audio
+    // device may not run at the rate asked for, so this is checked again once it is open.
+    pub(crate) fn check_frequency(&self, sample_rate: i32) -> Result<(), String> {
+        let max_frequency = sample_rate as f32 / 2.0;
+        if !(self.frequency > 0.0 && self.frequency < max_frequency) {
+            return Err(format!("The buzzer frequency must be above 0 and below {} Hz.", max_frequency));
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct Buzzer {
+    sample_rate: f32,
+    settings: BuzzerSettings,
+    phase_inc: f32,
+    phase: f32,
+    noise_state: u16,
+    noise_value: f32,
+
+    // The tone is gated instead of paused, so the envelope can fade it out.
+    is_gate_open: bool,
+    is_muted: bool,
+    envelope: f32,
+
+    // Number of samples generated so far. Gate changes are scheduled against this clock.
+    sample_clock: u64,
+    gate_changes: VecDeque<(u64, bool)>,
+    // Where the next emulated frame starts on the sample clock, and how far ahead of
+    // the samples being played it is scheduled.
+    next_frame_start: Option<f64>,
+    latency: f64,
+
+    registers: SoundRegisters,
+    pending_pattern: Option<Option<[u8; AUDIO_PATTERN_LEN]>>,
+    // Position inside the pattern, in bits.
+    pattern_position: f32,
+}
+
+impl Buzzer {
+    pub(crate) fn new(sample_rate: i32, settings: BuzzerSettings, latency_samples: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            settings,
+            phase_inc: settings.frequency / sample_rate as f32,
+            phase: 0.0,
+            noise_state: 0x0001,
+            noise_value: 1.0,
+
+            is_gate_open: false,
+            is_muted: false,
+            envelope: 0.0,
+
+            sample_clock: 0,
+            gate_changes: VecDeque::new(),
+            next_frame_start: None,
+            latency: latency_samples as f64,
+
+            registers: SoundRegisters::new(),
+            pending_pattern: None,
+            pattern_position: 0.0,
+        }
+    }
+
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    pub(crate) fn set_muted(&mut self, is_muted: bool) {
+        self.is_muted = is_muted;
+    }
+
+    // Schedules the sound events of an emulated frame that lasts `frame_samples` samples.
+    // Frames are laid out back to back, so the events keep their exact spacing, unless the
+    // emulation falls behind or runs too far ahead of the audio.
+    pub(crate) fn schedule_frame(&mut self, events: &[SoundEvent], clocks_per_frame: u32, frame_samples: f64) {
+        let earliest_start = self.sample_clock as f64 + self.latency;
+        let latest_start = earliest_start + self.latency.max(frame_samples) * 4.0;
+
+        let frame_start = match self.next_frame_start {
+            Some(start) if (earliest_start..=latest_start).contains(&start) => start,
+            _ => earliest_start,
+        };
+        self.next_frame_start = Some(frame_start + frame_samples);
+
+        for event in events {
+            let event_start = frame_start + event.clock as f64 / clocks_per_frame as f64 * frame_samples;
+            let event_end = event_start + event.duration as f64 * frame_samples;
+
+            // Writing to the sound timer overrides anything scheduled after the write.
+            let event_start = event_start.round() as u64;
+            while matches!(self.gate_changes.back(), Some((time, _)) if *time >= event_start) {
+                self.gate_changes.pop_back();
+            }
+
+            if event.duration > 0 {
+                self.gate_changes.push_back((event_start, true));
+                self.gate_changes.push_back((event_end.round() as u64, false));
+            } else {
+                self.gate_changes.push_back((event_start, false));
+            }
+        }
+    }
+
+    pub(crate) fn set_registers(&mut self, registers: SoundRegisters) {
+        self.registers.pitch = registers.pitch;
+
+        // Swapping the pattern right away could cut a bit short. The new pattern is picked up
+        // at the next bit boundary instead, keeping the playback position, so there is no click.
+        if registers.pattern != self.registers.pattern {
+            self.pending_pattern = Some(registers.pattern);
+        }
+    }
+
+    pub(crate) fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            while let Some((_, is_open)) = self.gate_changes.front().filter(|(time, _)| *time <= self.sample_clock) {
+                self.is_gate_open = *is_open;
+                self.gate_changes.pop_front();
+            }
+            self.sample_clock += 1;
+
+            let sample = match self.registers.pattern {
+                Some(pattern) => self.next_pattern_sample(&pattern),
+                None => self.next_tone_sample(),
+            };
+            *x = sample * self.settings.volume * self.next_envelope_level();
+
+            if self.registers.pattern.is_none() {
+                if let Some(pattern) = self.pending_pattern.take() {
+                    self.registers.pattern = pattern;
+                }
+            }
+        }
+    }
+
+    fn next_envelope_level(&mut self) -> f32 {
+        if self.is_gate_open && !self.is_muted {
+            let attack_samples = self.settings.attack_ms / 1000.0 * self.sample_rate;
+            self.envelope = if attack_samples < 1.0 { 1.0 } else { (self.envelope + 1.0 / attack_samples).min(1.0) };
+        } else {
+            let release_samples = self.settings.release_ms / 1000.0 * self.sample_rate;
+            self.envelope = if release_samples < 1.0 { 0.0 } else { (self.envelope - 1.0 / release_samples).max(0.0) };
+        }
+
+        self.envelope
+    }
+
+    fn next_tone_sample(&mut self) -> f32 {
+        // The hard edges of the square and pulse waves are smoothed with polyBLEP, so they
+        // don't alias. The other waveforms have little energy in their high harmonics.
+        let phase = self.phase;
+        let phase_inc = self.phase_inc;
+        let sample = match self.settings.waveform {
+            Waveform::Square => {
+                let naive = if phase < 0.5 { 1.0 } else { -1.0 };
+
+                naive + poly_blep(phase, phase_inc) - poly_blep((phase + 0.5) % 1.0, phase_inc)
+            },
+            Waveform::Pulse => {
+                let pulse_width = self.settings.pulse_width;
+                let naive = if phase < pulse_width { 1.0 } else { -1.0 };
+
+                naive + poly_blep(phase, phase_inc) - poly_blep((phase + 1.0 - pulse_width) % 1.0, phase_inc)
+            },
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Noise => self.noise_value,
+        };
+
+        self.phase += self.phase_inc;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+
+            // The noise is sampled and held once per period, so the frequency still sets its pitch.
+            if self.settings.waveform == Waveform::Noise {
+                self.noise_value = self.next_noise_value();
+            }
+        }
+
+        sample
+    }
+
+    // A 15 bit linear feedback shift register, like the noise channel of many sound chips.
+    fn next_noise_value(&mut self) -> f32 {
+        let feedback = (self.noise_state ^ (self.noise_state >> 1)) & 1;
+        self.noise_state = (self.noise_state >> 1) | (feedback << 14);
+
+        if self.noise_state & 1 != 0 { 1.0 } else { -1.0 }
+    }
+
+    fn next_pattern_sample(&mut self, pattern: &[u8; AUDIO_PATTERN_LEN]) -> f32 {
+        let position = self.pattern_position;
+        let bit_index = position as usize;
+        let bit_inc = self.registers.playback_rate() / self.sample_rate;
+
+        let level_at = |bit_index: isize| {
+            let bit_index = bit_index.rem_euclid(AUDIO_PATTERN_BITS as isize) as usize;
+            if pattern[bit_index / 8] & (0x80 >> (bit_index % 8)) != 0 { 1.0 } else { -1.0 }
+        };
+
+        // Every bit boundary closer than one sample is a step to smooth. At high pitches a
+        // sample can span several bits, so all of them are accounted for.
+        let mut sample = level_at(bit_index as isize);
+        let first_boundary = (position - bit_inc).ceil() as isize;
+        let last_boundary = (position + bit_inc).floor() as isize;
+        for boundary in first_boundary..=last_boundary {
+            let step = level_at(boundary) - level_at(boundary - 1);
+            if step != 0.0 {
+                sample += step / 2.0 * poly_blep_step((position - boundary as f32) / bit_inc);
+            }
+        }
+
+        self.pattern_position = (position + bit_inc) % AUDIO_PATTERN_BITS;
+
+        if self.pattern_position as usize != bit_index {
+            if let Some(new_pattern) = self.pending_pattern.take() {
+                self.registers.pattern = new_pattern;
+            }
+        }
+
+        sample
+    }
+}
+
+// The polyBLEP residual of a rising edge at phase 0 of a periodic wave,
+// with `phase_inc` being the phase advanced per sample.
+fn poly_blep(phase: f32, phase_inc: f32) -> f32 {
+    if phase < phase_inc {
+        poly_blep_step(phase / phase_inc)
+    } else if phase > 1.0 - phase_inc {
+        poly_blep_step((phase - 1.0) / phase_inc)
+    } else {
+        0.0
+    }
+}
+
+// The polyBLEP residual of a step from -1 to 1, `distance` samples away from it (-1 to 1).
+// Adding it to the naive step turns it into a band-limited one.
+fn poly_blep_step(distance: f32) -> f32 {
+    if distance >= 1.0 || distance <= -1.0 {
+        0.0
+    } else if distance >= 0.0 {
+        2.0 * distance - distance * distance - 1.0
+    } else {
+        distance * distance + 2.0 * distance + 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_from(text: &str) -> Result<BuzzerSettings, String> {
+        BuzzerSettings::from_config(&Config::parse(text).unwrap())
+    }
+
+    #[test]
+    fn frequencies_must_stay_below_half_the_sample_rate() {
+        assert!(settings_from("[audio]\nfrequency = 22000").is_ok());
+        assert!(settings_from("[audio]\nfrequency = 22050").is_err());
+        assert!(settings_from("[audio]\nfrequency = 50000").is_err());
+        assert!(settings_from("[audio]\nfrequency = 0").is_err());
+        assert!(settings_from("[audio]\nfrequency = nan").is_err());
+        assert!(settings_from("[audio]\nfrequency = inf").is_err());
+    }
+
+    #[test]
+    fn nan_and_infinite_settings_are_rejected() {
+        assert!(settings_from("[audio]\npulse_width = 0.5\nvolume = 1\nattack_ms = 0\nrelease_ms = 100").is_ok());
+        for line in ["pulse_width = nan", "volume = nan", "volume = inf", "attack_ms = nan", "attack_ms = inf", "release_ms = nan", "release_ms = inf"] {
+            assert!(settings_from(&format!("[audio]\n{}", line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn frequency_is_checked_against_the_device_rate() {
+        let settings = BuzzerSettings { frequency: 15000.0, ..BuzzerSettings::default() };
+        assert!(settings.check_frequency(44100).is_ok());
+        assert!(settings.check_frequency(22050).is_err());
+    }
+
+    #[test]
+    fn phase_stays_within_a_period() {
+        // Settings don't go through the config check here, as if set by the program itself.
+        for waveform in [Waveform::Square, Waveform::Triangle, Waveform::Noise] {
+            let settings = BuzzerSettings { frequency: SAMPLE_RATE as f32 * 2.5, waveform, ..BuzzerSettings::default() };
+            let mut buzzer = Buzzer::new(SAMPLE_RATE as i32, settings, 0);
+
+            for _ in 0..1000 {
+                let sample = buzzer.next_tone_sample();
+                assert!((0.0..1.0).contains(&buzzer.phase));
+                assert!((-3.0..=3.0).contains(&sample));
+            }
+        }
+    }
+
+    // Share of a tone's energy that may fall outside of its harmonics. Without polyBLEP,
+    // the tones below leave 8 to 19% there.
+    const MAX_ALIASED_ENERGY: f64 = 0.02;
+    const SPECTRUM_SAMPLES: usize = 4096;
+    // Bins on each side of a harmonic that still belong to it, covering the window's main lobe.
+    const HARMONIC_HALF_WIDTH: f64 = 3.0;
+
+    fn render_tone(settings: BuzzerSettings, registers: SoundRegisters) -> Vec<f32> {
+        let mut buzzer = Buzzer::new(SAMPLE_RATE as i32, BuzzerSettings { attack_ms: 0.0, ..settings }, 0);
+        buzzer.set_registers(registers);
+        buzzer.is_gate_open = true;
+
+        // The first samples are skipped, so the pattern has been picked up.
+        let mut samples = vec![0.0; SPECTRUM_SAMPLES + 256];
+        buzzer.fill(&mut samples);
+
+        samples.split_off(256)
+    }
+
+    // The share of the energy that isn't near a multiple of `fundamental`, from a Hann
+    // windowed DFT up to half the sample rate.
+    fn aliased_energy(samples: &[f32], fundamental: f64) -> f64 {
+        let len = samples.len();
+        let bin_frequency = SAMPLE_RATE as f64 / len as f64;
+        let angles = (0..len).map(|index| std::f64::consts::TAU * index as f64 / len as f64).collect::<Vec<_>>();
+        let windowed = samples.iter()
+            .zip(&angles)
+            .map(|(sample, angle)| *sample as f64 * (0.5 - 0.5 * angle.cos()))
+            .collect::<Vec<_>>();
+
+        let mut total_energy = 0.0;
+        let mut aliased_energy = 0.0;
+        for bin in 0..=len / 2 {
+            let (mut real, mut imaginary) = (0.0, 0.0);
+            for (index, sample) in windowed.iter().enumerate() {
+                let angle = angles[bin * index % len];
+                real += sample * angle.cos();
+                imaginary -= sample * angle.sin();
+            }
+            let energy = real * real + imaginary * imaginary;
+
+            let harmonic = (bin as f64 * bin_frequency / fundamental).round();
+            let distance = (bin as f64 - harmonic * fundamental / bin_frequency).abs();
+
+            total_energy += energy;
+            if distance > HARMONIC_HALF_WIDTH {
+                aliased_energy += energy;
+            }
+        }
+
+        aliased_energy / total_energy
+    }
+
+    #[test]
+    fn high_square_tone_does_not_alias() {
+        let settings = BuzzerSettings { frequency: 5000.0, waveform: Waveform::Square, ..BuzzerSettings::default() };
+        let aliased = aliased_energy(&render_tone(settings, SoundRegisters::new()), 5000.0);
+
+        assert!(aliased < MAX_ALIASED_ENERGY, "{}", aliased);
+    }
+
+    #[test]
+    fn high_pulse_tone_does_not_alias() {
+        let settings = BuzzerSettings { frequency: 5000.0, waveform: Waveform::Pulse, ..BuzzerSettings::default() };
+        let aliased = aliased_energy(&render_tone(settings, SoundRegisters::new()), 5000.0);
+
+        assert!(aliased < MAX_ALIASED_ENERGY, "{}", aliased);
+    }
+
+    #[test]
+    fn high_pattern_tone_does_not_alias() {
+        // At pitch 208 the pattern plays 32000 bits per second, so two bits on and two off
+        // make an 8000 Hz square wave.
+        let registers = SoundRegisters { pattern: Some([0xCC; AUDIO_PATTERN_LEN]), pitch: 208 };
+        let aliased = aliased_energy(&render_tone(BuzzerSettings::default(), registers), 8000.0);
+
+        assert!(aliased < MAX_ALIASED_ENERGY, "{}", aliased);
+    }
+}
@@ -0,0 +1,366 @@
+// The menu shown when no ROM is given on the command line. It lists the ROMs of a directory,
+// narrowed down by typing, with details from the ROM database when it knows the ROM:
+//
+//     [browser]
+//     rom_directory = roms
+//     database = roms/database.cfg
+//
+// The database uses the config file format, with a section per ROM file name:
+//
+//     [PONG]
+//     title = Pong
+//     platform = CHIP-8
+//     instructions_per_frame = 10
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::frontend::Frontend;
+use crate::{Event, FRAME_DURATION};
+
+const DEFAULT_ROM_DIRECTORY: &str = "roms";
+const DATABASE_FILE_NAME: &str = "database.cfg";
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+// What a frontend needs to draw the menu.
+pub(crate) struct MenuView {
+    pub(crate) title: String,
+    pub(crate) filter: String,
+    pub(crate) items: Vec<String>,
+    pub(crate) selected: Option<usize>,
+    // Details of the selected item, one per line.
+    pub(crate) details: Vec<String>,
+}
+
+impl MenuView {
+    // The items that fit in `max_items` lines, scrolled to keep the selected item visible.
+    pub(crate) fn visible_range(&self, max_items: usize) -> Range<usize> {
+        let selected = self.selected.unwrap_or(0);
+        let start = selected.saturating_sub(max_items / 2).min(self.items.len().saturating_sub(max_items));
+
+        start..(start + max_items).min(self.items.len())
+    }
+}
+
+struct RomEntry {
+    path: String,
+    file_name: String,
+    title: Option<String>,
+    platform: Option<String>,
+    instructions_per_frame: Option<String>,
+}
+
+impl RomEntry {
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+
+        self.file_name.to_lowercase().contains(&filter)
+            || self.title.as_ref().is_some_and(|title| title.to_lowercase().contains(&filter))
+    }
+}
+
+struct RomBrowser {
+    directory: String,
+    entries: Vec<RomEntry>,
+    filter: String,
+    // Index into the entries matching the filter.
+    selected: usize,
+    // Why the directory couldn't be listed. The menu still opens, so a ROM can be dropped on it.
+    error: Option<String>,
+}
+
+impl RomBrowser {
+    fn new(directory: &str, database: &Config) -> Self {
+        let directory_entries = match fs::read_dir(directory) {
+            Ok(directory_entries) => directory_entries,
+            Err(error) => {
+                return Self {
+                    directory: directory.to_string(),
+                    entries: Vec::new(),
+                    filter: String::new(),
+                    selected: 0,
+                    error: Some(format!("Unable to list the ROMs in {}: {}", directory, error)),
+                };
+            },
+        };
+
+        let mut entries = directory_entries
+            .filter_map(|directory_entry| directory_entry.ok())
+            .map(|directory_entry| directory_entry.path())
+            .filter(|path| path.is_file() && is_rom_path(path))
+            .map(|path| {
+                let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                RomEntry {
+                    path: path.to_string_lossy().into_owned(),
+                    title: database.get(&file_name, "title").map(str::to_string),
+                    platform: database.get(&file_name, "platform").map(str::to_string),
+                    instructions_per_frame: database.get(&file_name, "instructions_per_frame").map(str::to_string),
+                    file_name,
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.file_name.to_lowercase());
+
+        Self {
+            directory: directory.to_string(),
+            entries,
+            filter: String::new(),
+            selected: 0,
+            error: None,
+        }
+    }
+
+    fn matching_entries(&self) -> Vec<&RomEntry> {
+        self.entries.iter().filter(|entry| entry.matches(&self.filter)).collect()
+    }
+
+    // Returns the path of the ROM, once one is chosen.
+    fn handle_event(&mut self, event: Event) -> Option<String> {
+        let matching_count = self.matching_entries().len();
+
+        match event {
+            Event::MenuUp => {
+                self.selected = self.selected.saturating_sub(1);
+            },
+            Event::MenuDown => {
+                self.selected = (self.selected + 1).min(matching_count.saturating_sub(1));
+            },
+            Event::MenuText(text) => {
+                self.filter.push_str(&text);
+                self.selected = 0;
+            },
+            Event::MenuBackspace => {
+                self.filter.pop();
+                self.selected = 0;
+            },
+            Event::MenuSelect => {
+                return self.matching_entries().get(self.selected).map(|entry| entry.path.clone());
+            },
+            Event::LoadRom(path) => {
+                return Some(path);
+            },
+            _ => { }
+        }
+
+        None
+    }
+
+    fn view(&self) -> MenuView {
+        let matching_entries = self.matching_entries();
+        let selected_entry = matching_entries.get(self.selected);
+
+        let mut details = Vec::new();
+        if let Some(error) = &self.error {
+            details.push(error.clone());
+        }
+        if let Some(entry) = selected_entry {
+            if let Some(title) = &entry.title {
+                details.push(format!("Title: {}", title));
+            }
+            if let Some(platform) = &entry.platform {
+                details.push(format!("Platform: {}", platform));
+            }
+            if let Some(instructions_per_frame) = &entry.instructions_per_frame {
+                details.push(format!("Speed: {} instructions/frame", instructions_per_frame));
+            }
+        }
+
+        MenuView {
+            title: format!("ROMs in {}", self.directory),
+            filter: self.filter.clone(),
+            items: matching_entries.iter()
+                .map(|entry| entry.title.clone().unwrap_or_else(|| entry.file_name.clone()))
+                .collect(),
+            selected: selected_entry.map(|_| self.selected),
+            details,
+        }
+    }
+}
+
+pub(crate) fn rom_directory(config: &Config) -> String {
+    config.get("browser", "rom_directory").unwrap_or(DEFAULT_ROM_DIRECTORY).to_string()
+}
+
+// A missing database is fine, the ROMs are just listed by file name.
+pub(crate) fn load_database(config: &Config) -> Result<Config, String> {
+    match config.get("browser", "database") {
+        Some(path) => Config::load(path, true),
+        None => Config::load(&Path::new(&rom_directory(config)).join(DATABASE_FILE_NAME).to_string_lossy(), false),
+    }
+}
+
+// Shows the menu until a ROM is chosen, from the list or by dropping a file on the window.
+// Returns None if the user quits instead.
+pub(crate) fn choose_rom(config: &Config, database: &Config, frontend: &mut dyn Frontend) -> Option<String> {
+    let mut browser = RomBrowser::new(&rom_directory(config), database);
+
+    loop {
+        for event in frontend.handle_event_loop() {
+            if event == Event::Quit {
+                return None;
+            }
+
+            if let Some(path) = browser.handle_event(event) {
+                return Some(path);
+            }
+        }
+
+        frontend.show_menu(&browser.view());
+        thread::sleep(Duration::from_secs_f64(FRAME_DURATION));
+    }
+}
+
+fn is_rom_path(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempRomDirectory {
+        path: String,
+    }
+
+    impl TempRomDirectory {
+        fn new(name: &str, file_names: &[&str]) -> Self {
+            let path = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            for file_name in file_names {
+                fs::write(path.join(file_name), [0x12, 0x00]).unwrap();
+            }
+
+            Self { path: path.to_string_lossy().into_owned() }
+        }
+    }
+
+    impl Drop for TempRomDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    // Listed as breakout.sc8, Table Tennis (pong.ch8), Tetris.c8.
+    fn browser_in(directory: &TempRomDirectory) -> RomBrowser {
+        let database = Config::parse("[pong.ch8]\ntitle = Table Tennis\nplatform = CHIP-8").unwrap();
+        RomBrowser::new(&directory.path, &database)
+    }
+
+    fn type_text(browser: &mut RomBrowser, text: &str) {
+        assert_eq!(browser.handle_event(Event::MenuText(text.to_string())), None);
+    }
+
+    #[test]
+    fn roms_are_listed_by_title_or_file_name() {
+        let directory = TempRomDirectory::new("listing", &["pong.ch8", "Tetris.c8", "breakout.sc8", "notes.txt"]);
+        let mut browser = browser_in(&directory);
+        let view = browser.view();
+
+        assert_eq!(view.items, vec!["breakout.sc8", "Table Tennis", "Tetris.c8"]);
+        assert_eq!(view.selected, Some(0));
+        assert!(view.details.is_empty());
+
+        browser.handle_event(Event::MenuDown);
+        assert_eq!(browser.view().details, vec!["Title: Table Tennis", "Platform: CHIP-8"]);
+    }
+
+    #[test]
+    fn filter_matches_file_names_and_titles() {
+        let directory = TempRomDirectory::new("filter", &["pong.ch8", "Tetris.c8", "breakout.sc8"]);
+        let mut browser = browser_in(&directory);
+
+        type_text(&mut browser, "TENNIS");
+        assert_eq!(browser.view().items, vec!["Table Tennis"]);
+
+        for _ in 0.."TENNIS".len() {
+            browser.handle_event(Event::MenuBackspace);
+        }
+        type_text(&mut browser, "tris");
+        assert_eq!(browser.view().items, vec!["Tetris.c8"]);
+
+        type_text(&mut browser, "x");
+        assert!(browser.view().items.is_empty());
+        assert_eq!(browser.view().selected, None);
+        assert_eq!(browser.handle_event(Event::MenuSelect), None);
+    }
+
+    #[test]
+    fn selection_is_clamped_to_the_matching_roms() {
+        let directory = TempRomDirectory::new("clamping", &["pong.ch8", "Tetris.c8", "breakout.sc8"]);
+        let mut browser = browser_in(&directory);
+
+        browser.handle_event(Event::MenuUp);
+        assert_eq!(browser.view().selected, Some(0));
+
+        for _ in 0..5 {
+            browser.handle_event(Event::MenuDown);
+        }
+        assert_eq!(browser.view().selected, Some(2));
+    }
+
+    #[test]
+    fn editing_the_filter_resets_the_selection() {
+        let directory = TempRomDirectory::new("backspace", &["pong.ch8", "Tetris.c8", "breakout.sc8"]);
+        let mut browser = browser_in(&directory);
+
+        browser.handle_event(Event::MenuDown);
+        browser.handle_event(Event::MenuDown);
+        type_text(&mut browser, "t");
+        assert_eq!(browser.view().selected, Some(0));
+
+        browser.handle_event(Event::MenuDown);
+        browser.handle_event(Event::MenuBackspace);
+        assert_eq!(browser.view().selected, Some(0));
+        assert_eq!(browser.view().filter, "");
+    }
+
+    #[test]
+    fn selecting_after_filtering_picks_the_matching_rom() {
+        let directory = TempRomDirectory::new("select", &["pong.ch8", "Tetris.c8", "breakout.sc8"]);
+        let mut browser = browser_in(&directory);
+
+        type_text(&mut browser, "te");
+        assert_eq!(browser.view().items, vec!["Table Tennis", "Tetris.c8"]);
+        browser.handle_event(Event::MenuDown);
+
+        let path = browser.handle_event(Event::MenuSelect).unwrap();
+        assert_eq!(Path::new(&path), Path::new(&directory.path).join("Tetris.c8"));
+    }
+
+    #[test]
+    fn visible_range_scrolls_to_keep_the_selection_in_view() {
+        let view_with = |selected| MenuView {
+            title: String::new(),
+            filter: String::new(),
+            items: (0..10).map(|index| index.to_string()).collect(),
+            selected,
+            details: Vec::new(),
+        };
+
+        assert_eq!(view_with(None).visible_range(4), 0..4);
+        assert_eq!(view_with(Some(1)).visible_range(4), 0..4);
+        assert_eq!(view_with(Some(5)).visible_range(4), 3..7);
+        assert_eq!(view_with(Some(9)).visible_range(4), 6..10);
+        // Everything fits.
+        assert_eq!(view_with(Some(9)).visible_range(20), 0..10);
+    }
+
+    #[test]
+    fn missing_directory_shows_an_empty_menu_with_the_error() {
+        let mut browser = RomBrowser::new("/nonexistent/roms", &Config::default());
+        let view = browser.view();
+
+        assert!(view.items.is_empty());
+        assert_eq!(view.selected, None);
+        assert!(view.details[0].starts_with("Unable to list the ROMs in /nonexistent/roms"));
+
+        assert_eq!(browser.handle_event(Event::MenuSelect), None);
+        assert_eq!(browser.handle_event(Event::LoadRom("dropped.ch8".to_string())), Some("dropped.ch8".to_string()));
+    }
+}
@@ -0,0 +1,324 @@
+// The command line. Options can come in any order, written "--name value" or "--name=value",
+// while the positional arguments keep theirs.
+
+use std::str::FromStr;
+
+use crate::quirks::Quirks;
+
+pub(crate) const HELP: &str = "\
+A CHIP-8 interpreter.
+
+Usage:
+    chip8 [options] [<ROM path> [modern|vip]]
+    chip8 [options] --export <ROM path> <movie path> <frame count> <video.y4m> <audio.wav>
+    chip8 [options] --fingerprint <ROM path> <frame count> [<movie path>]
+
+Without a ROM path, a ROM is picked from the ROM browser.
+
+Options:
+    -h, --help                          Show this help.
+    --profile <modern|vip>              Quirk profile, modern by default.
+    --wrap-sprites                      Wrap sprites around the screen edges.
+    --instructions-per-frame <count>    Instructions run every frame.
+    --scale <factor>                    Size of a CHIP-8 pixel in the window.
+    --palette <background,foreground>   Colors, as hex, like 000000,ffffff.
+    --seed <number>                     Seed of the random number generator.
+    --keymap <path>                     File with the [keys] bindings to use.
+    --config <path>                     Config file, chip8.cfg by default.
+    --terminal                          Run in the terminal instead of a window.
+    --headless                          Run without any input or output.
+    --frames <count>                    Quit after this many emulated frames.
+    --record-movie <path>               Save the keys pressed as a movie on exit.
+    --play-movie <path>                 Take the keys from a movie.
+    --load-state <path>                 Start from a savestate.
+    --fast-forward <speed|uncapped>     Speed while Tab is held.
+    --volume <0-1>                      Buzzer volume.
+    --frequency <Hz>                    Buzzer frequency.
+    --waveform <shape>                  square, pulse, triangle, sine or noise.
+    --pulse-width <0-1>                 Duty cycle of the pulse waveform.
+    --attack <ms>                       Buzzer fade in time.
+    --release <ms>                      Buzzer fade out time.
+
+Keys:
+    Escape                  Quit.
+    F1, Pause               Pause or resume.
+    F2                      Run one frame, then pause.
+    F5                      Run one instruction, then pause.
+    Shift+F5                Save the state to <ROM path>.state.
+    Shift+F6                Load the state from <ROM path>.state.
+    F8                      Mute or unmute.
+    F9                      Restart the program, keeping the memory.
+    Shift+F9                Reload the ROM.
+    F10                     Start or stop recording a GIF.
+    F12                     Save a screenshot.
+    Page Up, Page Down      Change the speed.
+    Shift+Page Up/Down      Change the instructions per frame.
+    Tab                     Fast-forward while held.
+
+  In the window only:
+    F3                      Show the FPS and instructions per second.
+    F4                      Switch between texture and rectangle rendering.
+    F6                      Toggle the CRT effects.
+    F7                      Switch between integer and aspect-fit scaling.
+    F11                     Toggle fullscreen.
+";
+
+// Command line options that override a setting of the config file.
+const CONFIG_OPTIONS: [(&str, &str, &str); 9] = [
+    ("--frequency", "audio", "frequency"),
+    ("--waveform", "audio", "waveform"),
+    ("--pulse-width", "audio", "pulse_width"),
+    ("--volume", "audio", "volume"),
+    ("--attack", "audio", "attack_ms"),
+    ("--release", "audio", "release_ms"),
+    ("--fast-forward", "speed", "fast_forward"),
+    ("--scale", "display", "scale"),
+    ("--palette", "display", "palette"),
+];
+
+pub(crate) enum Command {
+    Run,
+    Export { movie_path: String, frame_count: u64, video_path: String, audio_path: String },
+    Fingerprint { frame_count: u64, movie_path: Option<String> },
+    Help,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum FrontendKind {
+    Sdl,
+    Terminal,
+    Headless,
+}
+
+pub(crate) struct Options {
+    pub(crate) command: Command,
+    pub(crate) rom_path: Option<String>,
+    pub(crate) quirks: Quirks,
+    pub(crate) instructions_per_frame: Option<u32>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) keymap_path: Option<String>,
+    pub(crate) config_path: Option<String>,
+    // Settings given on the command line, as (section, key, value).
+    pub(crate) config_overrides: Vec<(&'static str, &'static str, String)>,
+    pub(crate) frontend_kind: FrontendKind,
+    pub(crate) frame_limit: Option<u64>,
+    pub(crate) record_movie_path: Option<String>,
+    pub(crate) play_movie_path: Option<String>,
+    pub(crate) load_state_path: Option<String>,
+}
+
+// Errors are usage errors, meant to be followed by a pointer to --help.
+pub(crate) fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        command: Command::Run,
+        rom_path: None,
+        quirks: Quirks::default(),
+        instructions_per_frame: None,
+        seed: None,
+        keymap_path: None,
+        config_path: None,
+        config_overrides: Vec::new(),
+        frontend_kind: FrontendKind::Sdl,
+        frame_limit: None,
+        record_movie_path: None,
+        play_movie_path: None,
+        load_state_path: None,
+    };
+    let mut mode = None;
+    let mut positionals = Vec::new();
+    let mut profile = None;
+
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            positionals.push(arg.clone());
+            continue;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let is_flag = matches!(name, "-h" | "--help" | "--terminal" | "--headless" | "--wrap-sprites" | "--export" | "--fingerprint");
+        if is_flag && inline_value.is_some() {
+            return Err(format!("{} doesn't take a value.", name));
+        }
+
+        let mut value = || inline_value.clone()
+            .or_else(|| arg_iter.next().cloned())
+            .ok_or_else(|| format!("Missing value for {}.", name));
+
+        match name {
+            "-h" | "--help" => {
+                options.command = Command::Help;
+                return Ok(options);
+            },
+            "--terminal" => { options.frontend_kind = FrontendKind::Terminal },
+            "--headless" => { options.frontend_kind = FrontendKind::Headless },
+            "--wrap-sprites" => { options.config_overrides.push(("quirks", "wrap_sprites", "true".to_string())) },
+            "--export" | "--fingerprint" => {
+                if mode.is_some() {
+                    return Err("--export and --fingerprint can't be used together.".to_string());
+                }
+                mode = Some(name);
+            },
+            "--profile" => { profile = Some(value()?) },
+            "--instructions-per-frame" => {
+                let instructions_per_frame = parse_value::<u32>(name, &value()?)?;
+                if instructions_per_frame == 0 {
+                    return Err("The instructions per frame must be at least 1.".to_string());
+                }
+                options.instructions_per_frame = Some(instructions_per_frame);
+            },
+            "--seed" => { options.seed = Some(parse_value(name, &value()?)?) },
+            "--keymap" => { options.keymap_path = Some(value()?) },
+            "--config" => { options.config_path = Some(value()?) },
+            "--frames" => { options.frame_limit = Some(parse_value(name, &value()?)?) },
+            "--record-movie" => { options.record_movie_path = Some(value()?) },
+            "--play-movie" => { options.play_movie_path = Some(value()?) },
+            "--load-state" => { options.load_state_path = Some(value()?) },
+            _ => {
+                let (_, section, key) = CONFIG_OPTIONS.into_iter()
+                    .find(|(option, _, _)| *option == name)
+                    .ok_or_else(|| format!("Unknown option {}.", name))?;
+                options.config_overrides.push((section, key, value()?));
+            },
+        }
+    }
+
+    let mut positionals = positionals.into_iter();
+    options.rom_path = positionals.next();
+
+    match mode {
+        Some("--export") => {
+            let usage = "--export expects <ROM path> <movie path> <frame count> <video.y4m> <audio.wav>.";
+            let (Some(movie_path), Some(frame_count), Some(video_path), Some(audio_path), None) =
+                (positionals.next(), positionals.next(), positionals.next(), positionals.next(), positionals.next()) else {
+                return Err(usage.to_string());
+            };
+
+            options.command = Command::Export { movie_path, frame_count: parse_value("the frame count", &frame_count)?, video_path, audio_path };
+        },
+        Some(_) => {
+            let usage = "--fingerprint expects <ROM path> <frame count> [<movie path>].";
+            let (Some(frame_count), movie_path, None) = (positionals.next(), positionals.next(), positionals.next()) else {
+                return Err(usage.to_string());
+            };
+
+            options.command = Command::Fingerprint { frame_count: parse_value("the frame count", &frame_count)?, movie_path };
+        },
+        None => {
+            // The profile can also follow the ROM path, as in earlier versions.
+            if let Some(profile_name) = positionals.next() {
+                profile = profile.or(Some(profile_name));
+            }
+            if let Some(extra_arg) = positionals.next() {
+                return Err(format!("Unexpected argument {}.", extra_arg));
+            }
+            // Nobody can pick a ROM from the browser in a headless run.
+            if matches!(options.frontend_kind, FrontendKind::Headless) && options.rom_path.is_none() {
+                return Err("--headless needs a ROM path.".to_string());
+            }
+        },
+    }
+
+    if let Some(profile_name) = profile {
+        options.quirks = Quirks::from_profile_name(&profile_name)
+            .ok_or_else(|| format!("Unknown profile {}, expected modern or vip.", profile_name))?;
+    }
+
+    if options.record_movie_path.is_some() && options.play_movie_path.is_some() {
+        return Err("--record-movie and --play-movie can't be used together.".to_string());
+    }
+
+    Ok(options)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("Invalid value '{}' for {}.", value, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args = std::iter::once("chip8").chain(args.split_whitespace()).map(String::from).collect::<Vec<_>>();
+        parse_args(&args)
+    }
+
+    fn parse_error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("'{}' was accepted.", args),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn options_take_their_value_inline_or_as_the_next_argument() {
+        let options = parse("--seed 7 game.ch8 --frames=30 --volume 0.5 --palette=000000,ffffff").unwrap();
+
+        assert!(matches!(options.command, Command::Run));
+        assert_eq!(options.rom_path.as_deref(), Some("game.ch8"));
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.frame_limit, Some(30));
+        assert_eq!(options.config_overrides, vec![
+            ("audio", "volume", "0.5".to_string()),
+            ("display", "palette", "000000,ffffff".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn flags_are_recognized() {
+        assert!(matches!(parse("--terminal").unwrap().frontend_kind, FrontendKind::Terminal));
+        assert!(matches!(parse("--headless game.ch8").unwrap().frontend_kind, FrontendKind::Headless));
+        assert!(matches!(parse("game.ch8 -h --bad").unwrap().command, Command::Help));
+        assert_eq!(parse("--wrap-sprites").unwrap().config_overrides, vec![("quirks", "wrap_sprites", "true".to_string())]);
+        assert_eq!(parse("-").unwrap().rom_path.as_deref(), Some("-"));
+    }
+
+    #[test]
+    fn profile_can_follow_the_rom_path() {
+        assert!(!parse("game.ch8").unwrap().quirks.compatibility_mode);
+        assert!(parse("game.ch8 vip").unwrap().quirks.compatibility_mode);
+        assert!(parse("--profile vip game.ch8").unwrap().quirks.compatibility_mode);
+        // The option wins over the positional profile.
+        assert!(!parse("--profile modern game.ch8 vip").unwrap().quirks.compatibility_mode);
+
+        assert!(parse_error("game.ch8 amiga").contains("Unknown profile amiga"));
+        assert!(parse_error("--profile amiga").contains("Unknown profile amiga"));
+    }
+
+    #[test]
+    fn export_and_fingerprint_take_their_positional_arguments() {
+        let options = parse("--export game.ch8 game.movie 60 out.y4m out.wav").unwrap();
+        assert!(matches!(options.command, Command::Export { frame_count: 60, .. }));
+        assert_eq!(options.rom_path.as_deref(), Some("game.ch8"));
+
+        let options = parse("game.ch8 --fingerprint 60").unwrap();
+        assert!(matches!(options.command, Command::Fingerprint { frame_count: 60, movie_path: None }));
+        let options = parse("--fingerprint game.ch8 60 game.movie").unwrap();
+        assert!(matches!(options.command, Command::Fingerprint { movie_path: Some(_), .. }));
+
+        assert!(parse_error("--export game.ch8 game.movie 60 out.y4m").starts_with("--export expects"));
+        assert!(parse_error("--export game.ch8 game.movie 60 out.y4m out.wav extra").starts_with("--export expects"));
+        assert!(parse_error("--export game.ch8 game.movie sixty out.y4m out.wav").contains("the frame count"));
+        assert!(parse_error("--fingerprint game.ch8").starts_with("--fingerprint expects"));
+        assert!(parse_error("--fingerprint game.ch8 60 game.movie extra").starts_with("--fingerprint expects"));
+        assert!(parse_error("--export --fingerprint game.ch8 60").contains("can't be used together"));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert_eq!(parse_error("--terminal=yes"), "--terminal doesn't take a value.");
+        assert_eq!(parse_error("--wrap-sprites=true"), "--wrap-sprites doesn't take a value.");
+        assert_eq!(parse_error("game.ch8 --seed"), "Missing value for --seed.");
+        assert_eq!(parse_error("--volume"), "Missing value for --volume.");
+        assert_eq!(parse_error("--seed abc"), "Invalid value 'abc' for --seed.");
+        assert_eq!(parse_error("--instructions-per-frame 0"), "The instructions per frame must be at least 1.");
+        assert_eq!(parse_error("--unknown 1"), "Unknown option --unknown.");
+        assert_eq!(parse_error("game.ch8 vip extra"), "Unexpected argument extra.");
+        assert_eq!(parse_error("--headless"), "--headless needs a ROM path.");
+        assert!(parse_error("game.ch8 --record-movie a --play-movie b").contains("can't be used together"));
+    }
+}
@@ -0,0 +1,80 @@
+// Settings are read from an INI like file:
+//
+//     # Comments start with '#'
+//     [audio]
+//     volume = 0.1
+//     waveform = triangle
+//
+// Keys outside of any section belong to the "" section.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub(crate) const DEFAULT_CONFIG_PATH: &str = "chip8.cfg";
+
+#[derive(Default)]
+pub(crate) struct Config {
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Config {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = String::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(section_name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = section_name.trim().to_string();
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("Line {}: expected 'key = value'.", line_index + 1))?;
+            config.set(&section, key.trim(), value.trim());
+        }
+
+        Ok(config)
+    }
+
+    // A missing file is not an error, it just means nothing was configured.
+    pub(crate) fn load(path: &str, is_required: bool) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|error| format!("{}: {}", path, error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !is_required => Ok(Self::default()),
+            Err(error) => Err(format!("Unable to read {}: {}", path, error)),
+        }
+    }
+
+    pub(crate) fn set(&mut self, section: &str, key: &str, value: &str) {
+        self.sections
+            .entry(section.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    pub(crate) fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)?.get(key).map(String::as_str)
+    }
+
+    // All the keys set in a section, in no particular order.
+    pub(crate) fn keys(&self, section: &str) -> Vec<&str> {
+        match self.sections.get(section) {
+            Some(entries) => entries.keys().map(String::as_str).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_parsed<T: FromStr>(&self, section: &str, key: &str) -> Result<Option<T>, String> {
+        match self.get(section, key) {
+            Some(value) => value.parse::<T>()
+                .map(Some)
+                .map_err(|_| format!("Invalid value '{}' for {} in section [{}].", value, key, section)),
+            None => Ok(None),
+        }
+    }
+}
@@ -1,10 +1,19 @@
 use std::collections::HashSet;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::audio::{SoundEvent, SoundRegisters, AUDIO_PATTERN_LEN};
 use crate::display::Display;
 use crate::instruction::{Instruction, OpCode, OperandType};
+use crate::quirks::Quirks;
 
 const FONT_START_ADDR: usize = 0x50;
 const ROM_START_ADDR: usize = 0x200;
+const MEMORY_SIZE: usize = 4 * 1024;
+// Savestates start with this, the digit is bumped whenever their layout changes.
+const STATE_MAGIC: &[u8] = b"CH8STATE2";
+pub(crate) const MAX_ROM_SIZE: usize = MEMORY_SIZE - ROM_START_ADDR;
 
 const FONT_LINES_PER_CHAR: usize = 5;
 const DEFAULT_FONT: [u8; 16 * FONT_LINES_PER_CHAR] = [
@@ -29,37 +38,52 @@ const DEFAULT_FONT: [u8; 16 * FONT_LINES_PER_CHAR] = [
 pub(crate) struct Cpu {
     pub(crate) display: Display,
 
-    memory: [u8; 4 * 1024],
+    memory: [u8; MEMORY_SIZE],
     registers: [u8; 16],
     program_counter: u16,
     index_register: u16,
     delay_timer: u8,
     sound_timer: u8,
+    sound_registers: SoundRegisters,
+    sound_events: Vec<SoundEvent>,
+
+    // Instructions executed in the current frame.
+    frame_clock: u32,
 
     stack: Vec<u16>,
 
-    compatibility_mode: bool,
+    quirks: Quirks,
+    waiting_for_vblank: bool,
+
+    rng: StdRng,
 }
 
 impl Cpu {
-    pub(crate) const fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self { 
-            memory: [0; 4 * 1024], 
+            memory: [0; MEMORY_SIZE], 
             registers: [0; 16], 
             program_counter: 0, 
             index_register: 0, 
             delay_timer: 0, 
             sound_timer: 0,
+            sound_registers: SoundRegisters::new(),
+            sound_events: Vec::new(),
+
+            frame_clock: 0,
 
             stack: Vec::new(), 
             display: Display::new(),
 
-            compatibility_mode: false,
+            quirks: Quirks::new(),
+            waiting_for_vblank: false,
+
+            rng: StdRng::from_entropy(),
         }
     }
 
-    pub(crate) fn load_rom(&mut self, rom: &Vec<u8>, compatibity_mode: bool) {
-        self.compatibility_mode = compatibity_mode;
+    pub(crate) fn load_rom(&mut self, rom: &Vec<u8>, quirks: Quirks) {
+        self.quirks = quirks;
         self.program_counter = ROM_START_ADDR as u16;
         
         let mut font_addr = FONT_START_ADDR;
@@ -75,7 +99,118 @@ impl Cpu {
         }
     }
 
+    // Restarts the program, like the reset switch of the real machine: the CPU state and the
+    // screen are cleared, but the memory, and any change the program made to it, is kept.
+    pub(crate) fn reset(&mut self) {
+        self.registers = [0; 16];
+        self.program_counter = ROM_START_ADDR as u16;
+        self.index_register = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.sound_registers = SoundRegisters::new();
+        self.sound_events.clear();
+        self.frame_clock = 0;
+        self.stack.clear();
+        self.display.clear();
+        self.waiting_for_vblank = false;
+    }
+
+    // Makes CXNN deterministic, for reproducible runs.
+    pub(crate) fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Everything a program can observe, so it carries on from a saved state as if nothing
+    // happened. The random number generator is not part of it.
+    pub(crate) fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_MAGIC.len() + MEMORY_SIZE + self.display.data.len() + 64);
+
+        state.extend_from_slice(STATE_MAGIC);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.program_counter.to_le_bytes());
+        state.extend_from_slice(&self.index_register.to_le_bytes());
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.push(self.sound_registers.pitch);
+        state.push(self.sound_registers.pattern.is_some() as u8);
+        state.extend_from_slice(&self.sound_registers.pattern.unwrap_or([0; AUDIO_PATTERN_LEN]));
+        state.push(self.quirks.compatibility_mode as u8);
+        state.push(self.quirks.wrap_sprites as u8);
+        state.push(self.quirks.display_wait as u8);
+        state.push(self.waiting_for_vblank as u8);
+        state.extend_from_slice(&(self.stack.len() as u32).to_le_bytes());
+        for address in &self.stack {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+        state.extend_from_slice(&self.display.data);
+
+        state
+    }
+
+    // The machine is only changed once the whole state has been read.
+    pub(crate) fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = StateReader { state };
+
+        if reader.take(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err("Not a savestate, or one from an incompatible version.".to_string());
+        }
+
+        let mut memory = [0u8; MEMORY_SIZE];
+        memory.copy_from_slice(reader.take(MEMORY_SIZE)?);
+        let mut registers = [0u8; 16];
+        registers.copy_from_slice(reader.take(16)?);
+        let program_counter = reader.take_u16()?;
+        let index_register = reader.take_u16()?;
+        let delay_timer = reader.take_u8()?;
+        let sound_timer = reader.take_u8()?;
+        let pitch = reader.take_u8()?;
+        let has_pattern = reader.take_u8()? != 0;
+        let mut pattern = [0u8; AUDIO_PATTERN_LEN];
+        pattern.copy_from_slice(reader.take(AUDIO_PATTERN_LEN)?);
+        let quirks = Quirks {
+            compatibility_mode: reader.take_u8()? != 0,
+            wrap_sprites: reader.take_u8()? != 0,
+            display_wait: reader.take_u8()? != 0,
+        };
+        let waiting_for_vblank = reader.take_u8()? != 0;
+        let stack_len = reader.take_u32()?;
+        let stack = (0..stack_len).map(|_| reader.take_u16()).collect::<Result<Vec<_>, _>>()?;
+        let mut display = Display::new();
+        let display_data = reader.take(display.data.len())?;
+        display.data.copy_from_slice(display_data);
+
+        if !reader.state.is_empty() {
+            return Err("Unexpected data at the end of the savestate.".to_string());
+        }
+        if program_counter as usize >= MEMORY_SIZE - 1 {
+            return Err(format!("Invalid program counter in the savestate: {:#x}.", program_counter));
+        }
+
+        self.memory = memory;
+        self.registers = registers;
+        self.program_counter = program_counter;
+        self.index_register = index_register;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.sound_registers = SoundRegisters { pattern: if has_pattern { Some(pattern) } else { None }, pitch };
+        self.sound_events.clear();
+        self.quirks = quirks;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.frame_clock = 0;
+        self.stack = stack;
+        self.display = display;
+
+        Ok(())
+    }
+
     pub(crate) fn tick(&mut self, keys: HashSet<u8>) {
+        self.frame_clock += 1;
+
+        if self.waiting_for_vblank {
+            return;
+        }
+
         let pc = self.program_counter as usize;
         let instruction_hi = self.memory[pc];
         let instruction_lo = self.memory[pc + 1];
@@ -97,14 +232,16 @@ impl Cpu {
 
                 let sprite = &self.memory[sprite_start_index..(sprite_start_index + sprite_len)];
 
-                let has_colision = self.display.draw_sprite(x_coord, y_coord, sprite);
+                let has_colision = self.display.draw_sprite(x_coord, y_coord, sprite, self.quirks.wrap_sprites);
                 self.registers[0xF] = if has_colision { 0x01 } else { 0x00 };
+
+                self.waiting_for_vblank = self.quirks.display_wait;
             },
             OpCode::JumpAbsolute => {
                 self.program_counter = instruction.immediate_word;
             },
             OpCode::JumpWithOffset => {
-                let offset = if self.compatibility_mode { self.registers[0] } else { self.registers[instruction.x_register_index] };
+                let offset = if self.quirks.compatibility_mode { self.registers[0] } else { self.registers[instruction.x_register_index] };
 
                 let jump_target = instruction.immediate_word + (offset as u16);
                 self.program_counter = jump_target;
@@ -145,7 +282,7 @@ impl Cpu {
                     OperandType::Immediate => { instruction.immediate_byte },
                 };
 
-                let (result, carry) = operation.perform(lhs, rhs, self.compatibility_mode);
+                let (result, carry) = operation.perform(lhs, rhs, self.quirks.compatibility_mode);
                 self.registers[instruction.x_register_index] = result;
 
                 if let Some(carry_value) = carry {
@@ -168,6 +305,22 @@ impl Cpu {
             },
             OpCode::SetSoundRegister => {
                 self.sound_timer = self.registers[instruction.x_register_index];
+
+                // The instruction has already been counted, the write happens at its start.
+                self.sound_events.push(SoundEvent {
+                    clock: self.frame_clock - 1,
+                    duration: self.sound_timer,
+                });
+            },
+            OpCode::SetPitchRegister => {
+                self.sound_registers.pitch = self.registers[instruction.x_register_index];
+            },
+            OpCode::LoadAudioPattern => {
+                let pattern_start_index = self.index_register as usize;
+
+                let mut pattern = [0u8; AUDIO_PATTERN_LEN];
+                pattern.copy_from_slice(&self.memory[pattern_start_index..(pattern_start_index + AUDIO_PATTERN_LEN)]);
+                self.sound_registers.pattern = Some(pattern);
             },
             OpCode::ReadDelayRegister => {
                 self.registers[instruction.x_register_index] = self.delay_timer;
@@ -227,7 +380,7 @@ impl Cpu {
                 self.index_register += instruction.x_register_index as u16 + 1;
             },
             OpCode::Rand => {
-                self.registers[instruction.x_register_index] = rand::random::<u8>() & instruction.immediate_byte;
+                self.registers[instruction.x_register_index] = self.rng.gen::<u8>() & instruction.immediate_byte;
             },
             OpCode::Unknown(instruction_word) => {
                 panic!("Unknown instruction: 0x{:04X}", instruction_word);
@@ -235,7 +388,19 @@ impl Cpu {
         }
     }
 
-    pub(crate) fn decrement_timers(&mut self) -> bool {
+    pub(crate) fn sound_registers(&self) -> SoundRegisters {
+        self.sound_registers
+    }
+
+    pub(crate) fn take_sound_events(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.sound_events)
+    }
+
+    // Called once per frame, at the vertical blank.
+    pub(crate) fn decrement_timers(&mut self) {
+        self.waiting_for_vblank = false;
+        self.frame_clock = 0;
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -243,8 +408,117 @@ impl Cpu {
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
         }
+    }
+}
+
+// Reads a savestate front to back.
+struct StateReader<'a> {
+    state: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.state.len() < len {
+            return Err("The savestate is truncated.".to_string());
+        }
+
+        let (taken, rest) = self.state.split_at(len);
+        self.state = rest;
+
+        Ok(taken)
+    }
 
-        self.sound_timer > 0
+    fn take_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn take_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Calls a subroutine that draws the font sprite of V0, with a value in the sound
+    // registers and timers, then loops inside the subroutine.
+    const ROM: [u8; 18] = [
+        0x60, 0x07, 0xF0, 0x18, 0xF0, 0x15, 0xF0, 0x29,
+        0x22, 0x0C, 0x12, 0x0A, 0xD1, 0x15, 0x12, 0x0E,
+        0x00, 0xEE,
+    ];
+
+    fn running_cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&ROM.to_vec(), Quirks::vip());
+        for _ in 0..8 {
+            cpu.tick(HashSet::new());
+        }
+        cpu.decrement_timers();
+
+        cpu
+    }
+
+    #[test]
+    fn saved_state_loads_back_into_the_same_machine() {
+        let cpu = running_cpu();
+        let state = cpu.save_state();
+
+        let mut loaded_cpu = Cpu::new();
+        loaded_cpu.load_state(&state).unwrap();
+
+        assert!(loaded_cpu.save_state() == state);
+        assert_eq!(loaded_cpu.stack, vec![0x20A]);
+        assert_eq!(loaded_cpu.display.data, cpu.display.data);
+        assert_eq!(loaded_cpu.sound_timer, 6);
+        assert!(loaded_cpu.quirks.display_wait);
+    }
+
+    #[test]
+    fn truncated_state_is_rejected_without_changing_the_machine() {
+        let state = running_cpu().save_state();
+        let mut cpu = Cpu::new();
+        let state_before = cpu.save_state();
+
+        for len in [0, STATE_MAGIC.len(), state.len() / 2, state.len() - 1] {
+            assert!(cpu.load_state(&state[..len]).is_err());
+            assert!(cpu.save_state() == state_before);
+        }
+    }
+
+    #[test]
+    fn state_with_bad_magic_or_extra_data_is_rejected() {
+        let mut state = running_cpu().save_state();
+        let mut cpu = Cpu::new();
+
+        state.push(0);
+        assert!(cpu.load_state(&state).is_err());
+        state.pop();
+
+        state[0] = b'X';
+        assert!(cpu.load_state(&state).is_err());
+    }
+
+    #[test]
+    fn deep_stack_is_saved_whole() {
+        // A subroutine calling itself forever, deeper than a byte can count.
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&vec![0x22, 0x00], Quirks::default());
+        for _ in 0..300 {
+            cpu.tick(HashSet::new());
+        }
+
+        let mut loaded_cpu = Cpu::new();
+        loaded_cpu.load_state(&cpu.save_state()).unwrap();
+
+        assert_eq!(loaded_cpu.stack.len(), 300);
+        assert_eq!(loaded_cpu.stack, cpu.stack);
+    }
+}
@@ -0,0 +1,218 @@
+// CPU side post-processing that makes the screen look like a CRT. Every emulated pixel
+// becomes a `scale` x `scale` block of output pixels, so the effects have room to show.
+// Everything is computed with fixed rules from the display contents only, which keeps
+// the output deterministic.
+
+use crate::config::Config;
+use crate::display::{Display, Palette};
+
+// Larger scales make the CPU side rendering too slow to keep up with 60 Hz.
+const MAX_SCALE: usize = 16;
+
+#[derive(Clone, Copy)]
+pub(crate) struct CrtSettings {
+    pub(crate) scale: usize,
+    // How much the last line of every emulated pixel is darkened.
+    pub(crate) scanline_strength: f32,
+    // How much the two channels not matching an output column's subpixel are darkened.
+    pub(crate) mask_strength: f32,
+    // How much the first row and column of every emulated pixel are darkened.
+    pub(crate) grid_strength: f32,
+    // How much light lit and fading pixels spill into their neighbours.
+    pub(crate) bloom_strength: f32,
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        Self {
+            scale: 6,
+            scanline_strength: 0.5,
+            mask_strength: 0.2,
+            grid_strength: 0.15,
+            bloom_strength: 0.3,
+        }
+    }
+}
+
+impl CrtSettings {
+    // Reads the [crt] section of the config, keeping the defaults for anything missing.
+    pub(crate) fn from_config(config: &Config) -> Result<Self, String> {
+        let defaults = Self::default();
+
+        let settings = Self {
+            scale: config.get_parsed("crt", "scale")?.unwrap_or(defaults.scale),
+            scanline_strength: config.get_parsed("crt", "scanline_strength")?.unwrap_or(defaults.scanline_strength),
+            mask_strength: config.get_parsed("crt", "mask_strength")?.unwrap_or(defaults.mask_strength),
+            grid_strength: config.get_parsed("crt", "grid_strength")?.unwrap_or(defaults.grid_strength),
+            bloom_strength: config.get_parsed("crt", "bloom_strength")?.unwrap_or(defaults.bloom_strength),
+        };
+
+        if !(1..=MAX_SCALE).contains(&settings.scale) {
+            return Err(format!("The CRT scale must be between 1 and {}.", MAX_SCALE));
+        }
+        let strengths = [settings.scanline_strength, settings.mask_strength, settings.grid_strength, settings.bloom_strength];
+        if strengths.iter().any(|strength| !(0.0..=1.0).contains(strength)) {
+            return Err("The CRT effect strengths must be between 0 and 1.".to_string());
+        }
+
+        Ok(settings)
+    }
+}
+
+// Weights of a 3x3 gaussian blur, used for the bloom.
+const BLOOM_KERNEL: [[u32; 3]; 3] = [
+    [1, 2, 1],
+    [2, 4, 2],
+    [1, 2, 1],
+];
+const BLOOM_KERNEL_SUM: u32 = 16;
+
+// Returns an RGB24 image of (width * scale) x (height * scale) pixels.
+pub(crate) fn render(display: &Display, palette: &Palette, settings: &CrtSettings) -> Vec<u8> {
+    let width = display.width();
+    let height = display.height();
+    let scale = settings.scale.max(1);
+
+    let glow = bloom(display);
+
+    let output_width = width * scale;
+    let output_height = height * scale;
+    let mut result = Vec::with_capacity(output_width * output_height * 3);
+
+    for output_y in 0..output_height {
+        let (y_index, sub_y) = (output_y / scale, output_y % scale);
+
+        for output_x in 0..output_width {
+            let (x_index, sub_x) = (output_x / scale, output_x % scale);
+            let data_index = y_index * width + x_index;
+
+            let mut brightness = 1.0;
+            if scale > 1 && sub_y == scale - 1 {
+                brightness *= 1.0 - settings.scanline_strength;
+            }
+            if scale > 1 && (sub_x == 0 || sub_y == 0) {
+                brightness *= 1.0 - settings.grid_strength;
+            }
+
+            let color = palette.color_for(display.data[data_index]);
+            let glow_amount = glow[data_index] * settings.bloom_strength;
+
+            for (channel, component) in color.iter().enumerate() {
+                let mut value = *component as f32 * brightness;
+                if output_x % 3 != channel {
+                    value *= 1.0 - settings.mask_strength;
+                }
+                value += palette.foreground[channel] as f32 * glow_amount;
+
+                result.push(value.clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+
+    result
+}
+
+// Blurred pixel intensities, from 0.0 to 1.0. Fading pixels still glow, following the fade.
+fn bloom(display: &Display) -> Vec<f32> {
+    let width = display.width();
+    let height = display.height();
+    let mut result = vec![0.0; width * height];
+
+    for y_index in 0..height {
+        for x_index in 0..width {
+            let mut sum = 0u32;
+
+            for (kernel_y, kernel_line) in BLOOM_KERNEL.iter().enumerate() {
+                for (kernel_x, weight) in kernel_line.iter().enumerate() {
+                    let sample_x = x_index as isize + kernel_x as isize - 1;
+                    let sample_y = y_index as isize + kernel_y as isize - 1;
+
+                    if sample_x < 0 || sample_y < 0 || sample_x >= width as isize || sample_y >= height as isize {
+                        continue;
+                    }
+
+                    sum += weight * display.data[sample_y as usize * width + sample_x as usize] as u32;
+                }
+            }
+
+            result[y_index * width + x_index] = sum as f32 / (BLOOM_KERNEL_SUM * 0xFF) as f32;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_EFFECTS: CrtSettings = CrtSettings { scale: 3, scanline_strength: 0.0, mask_strength: 0.0, grid_strength: 0.0, bloom_strength: 0.0 };
+
+    // The screen with only its top left pixel lit.
+    fn corner_display() -> Display {
+        let mut display = Display::new();
+        display.data[0] = 0xFF;
+        display
+    }
+
+    fn output_pixel(image: &[u8], settings: &CrtSettings, x: usize, y: usize) -> [u8; 3] {
+        let index = (y * Display::new().width() * settings.scale + x) * 3;
+        [image[index], image[index + 1], image[index + 2]]
+    }
+
+    #[test]
+    fn scanlines_and_mask_darken_known_pixels() {
+        let settings = CrtSettings { scanline_strength: 0.5, mask_strength: 0.2, ..NO_EFFECTS };
+        let image = render(&corner_display(), &Palette::default(), &settings);
+
+        assert_eq!(image.len(), 64 * 3 * 32 * 3 * 3);
+        // Output column 1 shows the green subpixel, the other channels are masked.
+        assert_eq!(output_pixel(&image, &settings, 1, 1), [204, 255, 204]);
+        // The last line of the emulated pixel is a scanline.
+        assert_eq!(output_pixel(&image, &settings, 1, 2), [102, 127, 102]);
+        assert_eq!(output_pixel(&image, &settings, 3, 1), [0, 0, 0]);
+    }
+
+    #[test]
+    fn grid_darkens_the_first_row_and_column() {
+        let settings = CrtSettings { grid_strength: 0.5, ..NO_EFFECTS };
+        let image = render(&corner_display(), &Palette::default(), &settings);
+
+        assert_eq!(output_pixel(&image, &settings, 0, 1), [127, 127, 127]);
+        assert_eq!(output_pixel(&image, &settings, 1, 0), [127, 127, 127]);
+        assert_eq!(output_pixel(&image, &settings, 1, 1), [255, 255, 255]);
+    }
+
+    #[test]
+    fn bloom_spills_into_the_neighbours() {
+        let settings = CrtSettings { bloom_strength: 1.0, ..NO_EFFECTS };
+        let image = render(&corner_display(), &Palette::default(), &settings);
+
+        // A direct neighbour gets 2/16 of the light, a diagonal one 1/16.
+        assert_eq!(output_pixel(&image, &settings, 3, 0), [31, 31, 31]);
+        assert_eq!(output_pixel(&image, &settings, 3, 3), [15, 15, 15]);
+        assert_eq!(output_pixel(&image, &settings, 6, 0), [0, 0, 0]);
+    }
+
+    #[test]
+    fn settings_are_read_and_checked_from_the_config() {
+        let config = Config::parse("[crt]\nscale = 4\nbloom_strength = 0\n").unwrap();
+        let settings = CrtSettings::from_config(&config).unwrap();
+        assert_eq!(settings.scale, 4);
+        assert_eq!(settings.bloom_strength, 0.0);
+        assert_eq!(settings.mask_strength, CrtSettings::default().mask_strength);
+
+        for invalid in ["scale = 0", "scale = 17", "mask_strength = 1.5", "grid_strength = -0.1", "scanline_strength = dark"] {
+            let config = Config::parse(&format!("[crt]\n{}\n", invalid)).unwrap();
+            assert!(CrtSettings::from_config(&config).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn rendering_is_deterministic() {
+        let settings = CrtSettings::default();
+        let display = corner_display();
+
+        assert!(render(&display, &Palette::default(), &settings) == render(&display, &Palette::default(), &settings));
+    }
+}
@@ -1,7 +1,65 @@
+use std::str::FromStr;
+
+use crate::png;
+
 pub(crate) const DISPLAY_WIDTH: usize = 64;
 pub(crate) const DISPLAY_HEIGHT: usize = 32;
 
-const PIXEL_FADE_RATE: u8 = 8;
+// How much a pixel that was turned off fades every presented frame.
+const PIXEL_FADE_RATE: u8 = 128;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct Palette {
+    pub(crate) background: [u8; 3],
+    pub(crate) foreground: [u8; 3],
+}
+
+impl Palette {
+    // Faded pixels are drawn by blending between the background and the foreground colors.
+    pub(crate) fn color_for(&self, intensity: u8) -> [u8; 3] {
+        let mut color = [0u8; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let background = self.background[channel] as u32;
+            let foreground = self.foreground[channel] as u32;
+
+            *value = ((background * (0xFF - intensity as u32) + foreground * intensity as u32) / 0xFF) as u8;
+        }
+
+        color
+    }
+}
+
+// Written as two hex colors, "background,foreground", like "000000,ffffff".
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse_color = |color: &str| -> Result<[u8; 3], String> {
+            let color = color.trim().trim_start_matches('#');
+            let value = match color.len() {
+                6 => u32::from_str_radix(color, 16).ok(),
+                _ => None,
+            };
+
+            value
+                .map(|value| [(value >> 16) as u8, (value >> 8) as u8, value as u8])
+                .ok_or_else(|| format!("Invalid color '{}', expected 6 hex digits.", color))
+        };
+
+        let (background, foreground) = text.split_once(',')
+            .ok_or_else(|| format!("Invalid palette '{}', expected 'background,foreground'.", text))?;
+
+        Ok(Self { background: parse_color(background)?, foreground: parse_color(foreground)? })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self { background: [0x00, 0x00, 0x00], foreground: [0xFF, 0xFF, 0xFF] }
+    }
+}
+
+#[derive(Clone)]
 pub(crate) struct Display {
     // TODO: Replacing this array with a Vec would
     // enable us to easily implement multiple screen sizes.
@@ -15,6 +73,14 @@ impl Display {
         }
     }
 
+    pub(crate) fn width(&self) -> usize {
+        DISPLAY_WIDTH
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        DISPLAY_HEIGHT
+    }
+
     pub(crate) fn clear(&mut self) {
         self.data = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT]
     }
@@ -28,29 +94,83 @@ impl Display {
         }
     }
 
-    pub(crate) fn draw_sprite(&mut self, x_coord: u8, y_coord: u8, sprite: &[u8]) -> bool {
+    pub(crate) fn draw_sprite(&mut self, x_coord: u8, y_coord: u8, sprite: &[u8], wrap: bool) -> bool {
         let mut has_colision = false;
-        
-        let mut y_index = (y_coord as usize) % DISPLAY_HEIGHT;
-        for sprite_line in sprite {
-            if y_index >= DISPLAY_HEIGHT { break; }
 
-            let mut x_index = (x_coord as usize) % DISPLAY_WIDTH;
-            for column_index in 0..8u8 {
-                if x_index >= DISPLAY_WIDTH { break; }
+        // The starting position always wraps, only the sprite contents can be clipped.
+        let start_x = (x_coord as usize) % DISPLAY_WIDTH;
+        let start_y = (y_coord as usize) % DISPLAY_HEIGHT;
+
+        for (line_index, sprite_line) in sprite.iter().enumerate() {
+            let mut y_index = start_y + line_index;
+            if y_index >= DISPLAY_HEIGHT {
+                if !wrap { break; }
+
+                y_index %= DISPLAY_HEIGHT;
+            }
+
+            for column_index in 0..8usize {
+                let mut x_index = start_x + column_index;
+                if x_index >= DISPLAY_WIDTH {
+                    if !wrap { break; }
+
+                    x_index %= DISPLAY_WIDTH;
+                }
 
                 let pixel_mask = 0x80 >> column_index;
                 if sprite_line & pixel_mask != 0 {
                     has_colision |= self.flip_pixel_at(x_index, y_index);
                 }
+            }
+        }
+
+        has_colision
+    }
+
+    // A stable hash of the logical screen contents, meant for regression tests.
+    // Only lit pixels count, so the fade effect doesn't change the result.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
 
-                x_index += 1;
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut add_byte = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        };
+
+        for dimension in [self.width() as u16, self.height() as u16] {
+            dimension.to_le_bytes().into_iter().for_each(&mut add_byte);
+        }
+
+        for pixels in self.data.chunks(8) {
+            let mut packed = 0u8;
+            for (bit_index, pixel) in pixels.iter().enumerate() {
+                if *pixel == 0xFF {
+                    packed |= 0x80 >> bit_index;
+                }
             }
 
-            y_index += 1;
+            add_byte(packed);
         }
 
-        has_colision
+        hash
+    }
+
+    pub(crate) fn to_png(&self, scale: u32, palette: &Palette) -> Vec<u8> {
+        let scale = scale.max(1) as usize;
+        let width = DISPLAY_WIDTH * scale;
+        let height = DISPLAY_HEIGHT * scale;
+
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y_index in 0..height {
+            for x_index in 0..width {
+                let data_index = (y_index / scale) * DISPLAY_WIDTH + (x_index / scale);
+                pixels.extend_from_slice(&palette.color_for(self.data[data_index]));
+            }
+        }
+
+        png::encode_rgb(width as u32, height as u32, &pixels)
     }
 
     fn flip_pixel_at(&mut self, x: usize, y: usize) -> bool {
@@ -72,4 +192,88 @@ impl Default for Display {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x2 block, so a sprite at the last column or row sticks out by one pixel.
+    const BLOCK: [u8; 2] = [0xC0, 0xC0];
+
+    fn is_lit(display: &Display, (x, y): (usize, usize)) -> bool {
+        display.data[y * DISPLAY_WIDTH + x] == 0xFF
+    }
+
+    // Draws the block on a screen where only `lit_pixel` is on.
+    fn draw_block(x: u8, y: u8, wrap: bool, lit_pixel: Option<(usize, usize)>) -> (Display, bool) {
+        let mut display = Display::new();
+        if let Some((lit_x, lit_y)) = lit_pixel {
+            display.data[lit_y * DISPLAY_WIDTH + lit_x] = 0xFF;
+        }
+
+        let has_colision = display.draw_sprite(x, y, &BLOCK, wrap);
+        (display, has_colision)
+    }
+
+    // `inside` are the block pixels on the screen, `outside` where the others land when wrapping.
+    fn assert_clipped(x: u8, y: u8, inside: &[(usize, usize)], outside: &[(usize, usize)]) {
+        let (display, has_colision) = draw_block(x, y, false, None);
+        assert!(!has_colision);
+        assert!(inside.iter().all(|pixel| is_lit(&display, *pixel)));
+        assert!(outside.iter().all(|pixel| !is_lit(&display, *pixel)));
+        assert_eq!(display.data.iter().filter(|pixel| **pixel == 0xFF).count(), inside.len());
+
+        // Clipped pixels don't touch what is on the other side of the screen.
+        let (display, has_colision) = draw_block(x, y, false, Some(outside[0]));
+        assert!(!has_colision);
+        assert!(is_lit(&display, outside[0]));
+    }
+
+    fn assert_wrapped(x: u8, y: u8, inside: &[(usize, usize)], outside: &[(usize, usize)]) {
+        let (display, has_colision) = draw_block(x, y, true, None);
+        assert!(!has_colision);
+        assert!(inside.iter().chain(outside).all(|pixel| is_lit(&display, *pixel)));
+        assert_eq!(display.data.iter().filter(|pixel| **pixel == 0xFF).count(), BLOCK.len() * 2);
+
+        let (display, has_colision) = draw_block(x, y, true, Some(outside[0]));
+        assert!(has_colision);
+        assert!(!is_lit(&display, outside[0]));
+    }
+
+    #[test]
+    fn sprite_at_right_edge_is_clipped() {
+        assert_clipped(63, 10, &[(63, 10), (63, 11)], &[(0, 10), (0, 11)]);
+    }
+
+    #[test]
+    fn sprite_at_right_edge_wraps() {
+        assert_wrapped(63, 10, &[(63, 10), (63, 11)], &[(0, 10), (0, 11)]);
+    }
+
+    #[test]
+    fn sprite_at_bottom_edge_is_clipped() {
+        assert_clipped(10, 31, &[(10, 31), (11, 31)], &[(10, 0), (11, 0)]);
+    }
+
+    #[test]
+    fn sprite_at_bottom_edge_wraps() {
+        assert_wrapped(10, 31, &[(10, 31), (11, 31)], &[(10, 0), (11, 0)]);
+    }
+
+    #[test]
+    fn sprite_at_corner_is_clipped() {
+        assert_clipped(63, 31, &[(63, 31)], &[(0, 0), (0, 31), (63, 0)]);
+    }
+
+    #[test]
+    fn sprite_at_corner_wraps() {
+        assert_wrapped(63, 31, &[(63, 31)], &[(0, 0), (0, 31), (63, 0)]);
+    }
+
+    #[test]
+    fn sprite_position_always_wraps() {
+        let (display, _) = draw_block(64 + 10, 32 + 5, false, None);
+        assert!(is_lit(&display, (10, 5)));
+    }
+}
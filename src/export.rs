@@ -0,0 +1,92 @@
+// Headless rendering of a ROM driven by an input movie. Video and audio are written
+// frame by frame, so every video frame lines up with exactly SAMPLES_PER_FRAME samples.
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use crate::audio::{Buzzer, BuzzerSettings, SAMPLE_RATE};
+use crate::cpu::Cpu;
+use crate::display::Palette;
+use crate::movie::Movie;
+use crate::quirks::Quirks;
+use crate::wav::WavWriter;
+use crate::y4m::Y4mWriter;
+use crate::held_keys;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAMES_PER_SECOND) as usize;
+// Runs without --seed use this one, so the same ROM and movie always give the same result.
+pub(crate) const DEFAULT_SEED: u64 = 0;
+
+// The machine to run, for both exports and fingerprints.
+pub(crate) struct ExportSettings {
+    pub(crate) rom: Vec<u8>,
+    pub(crate) quirks: Quirks,
+    pub(crate) buzzer_settings: BuzzerSettings,
+    pub(crate) instructions_per_frame: u32,
+    pub(crate) seed: u64,
+}
+
+impl ExportSettings {
+    fn new_cpu(&self) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&self.rom, self.quirks);
+        cpu.seed_rng(self.seed);
+
+        cpu
+    }
+}
+
+pub(crate) fn run_export(settings: &ExportSettings, movie: &Movie, frame_count: u64, video_path: &str, audio_path: &str) -> io::Result<()> {
+    let palette = Palette::default();
+
+    let mut video = Y4mWriter::new(BufWriter::new(File::create(video_path)?))?;
+    let mut audio = WavWriter::new(BufWriter::new(File::create(audio_path)?), SAMPLE_RATE)?;
+    // Offline, nothing is playing yet, so the events don't need to be scheduled ahead.
+    let mut buzzer = Buzzer::new(SAMPLE_RATE as i32, settings.buzzer_settings, 0);
+    let mut samples = [0.0f32; SAMPLES_PER_FRAME];
+
+    let mut cpu = settings.new_cpu();
+
+    for frame in 0..frame_count {
+        run_frame(&mut cpu, settings.instructions_per_frame, movie.keys_at(frame));
+
+        video.write_frame(&cpu.display, &palette)?;
+
+        cpu.decrement_timers();
+
+        buzzer.set_registers(cpu.sound_registers());
+        buzzer.schedule_frame(&cpu.take_sound_events(), settings.instructions_per_frame, SAMPLES_PER_FRAME as f64);
+        buzzer.fill(&mut samples);
+        audio.write_samples(&samples)?;
+    }
+
+    video.finish()?;
+    audio.finish()?;
+
+    Ok(())
+}
+
+// Runs the ROM without any output and returns the screen fingerprint after every frame.
+pub(crate) fn run_fingerprints(settings: &ExportSettings, movie: &Movie, frame_count: u64) -> Vec<u64> {
+    let mut cpu = settings.new_cpu();
+
+    let mut result = Vec::with_capacity(frame_count as usize);
+    for frame in 0..frame_count {
+        run_frame(&mut cpu, settings.instructions_per_frame, movie.keys_at(frame));
+        result.push(cpu.display.fingerprint());
+
+        cpu.decrement_timers();
+    }
+
+    result
+}
+
+// Runs the instructions of a single frame. The timers are left for the caller to decrement,
+// after it has looked at the frame.
+fn run_frame(cpu: &mut Cpu, instructions_per_frame: u32, keys_held: [bool; 16]) {
+    cpu.display.fade_pixels();
+    for _ in 0..instructions_per_frame {
+        cpu.tick(held_keys(keys_held));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::audio::{SoundEvent, SoundRegisters};
+use crate::browser::MenuView;
+use crate::display::{Display, Palette};
+use crate::keymap::Keymap;
+use crate::Event;
+
+pub(crate) trait Frontend {
+    fn handle_event_loop(&mut self) -> Vec<Event>;
+
+    fn update_display(&mut self, display: &Display, palette: &Palette);
+
+    // Called at the end of every frame, with the sound events of that frame.
+    // `frame_duration` is the real time, in seconds, the frame lasts.
+    fn queue_sound_events(&mut self, events: &[SoundEvent], clocks_per_frame: u32, frame_duration: f64);
+
+    fn set_muted(&mut self, is_muted: bool);
+
+    fn set_sound_registers(&mut self, registers: SoundRegisters);
+
+    // A short description of the emulator state, such as the instructions per frame.
+    fn set_status(&mut self, _status: &str) { }
+
+    // Draws a menu instead of the emulated screen. Until the next `update_display`, input is
+    // sent as menu events instead of keypad keys.
+    fn show_menu(&mut self, _menu: &MenuView) { }
+
+    // Replaces the key bindings, when a different ROM is loaded.
+    fn set_keymap(&mut self, _keymap: Keymap) { }
+
+    fn set_paused(&mut self, _is_paused: bool) { }
+
+    // A short-lived message about something that just happened, such as a speed change.
+    fn show_message(&mut self, _message: &str) { }
+
+    // Measured over the last second of real time.
+    fn set_counters(&mut self, _frames_per_second: f64, _instructions_per_second: f64) { }
+
+    // Whether presenting a frame waits for the display refresh, which then paces the emulation.
+    fn is_vsynced(&self) -> bool {
+        false
+    }
+
+    // Whether the emulation should be slowed down to real time for this frontend.
+    fn is_realtime(&self) -> bool {
+        true
+    }
+
+    // Called once the emulation has ended, before the frontend is dropped.
+    fn finish(&mut self) { }
+}
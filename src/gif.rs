@@ -0,0 +1,275 @@
+// A minimal animated GIF encoder. Every frame uses the full 256 color global table,
+// so a display intensity can be used directly as a color index.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::display::{Display, Palette, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+const MIN_CODE_SIZE: u8 = 8;
+const MAX_CODE_SIZE: u8 = 12;
+const CLEAR_CODE: u16 = 1 << MIN_CODE_SIZE;
+const END_OF_INFORMATION_CODE: u16 = CLEAR_CODE + 1;
+const MAX_CODE_COUNT: u16 = 1 << MAX_CODE_SIZE;
+
+const FRAMES_PER_SECOND: u64 = 60;
+const CENTISECONDS_PER_SECOND: u64 = 100;
+// Viewers play shorter delays much slower than asked, often at 10 cs, so shorter frames are
+// merged into the next one.
+const MIN_DELAY_CS: u64 = 2;
+
+pub(crate) struct GifEncoder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub(crate) fn new(mut out: W, width: u16, height: u16, palette: &Palette) -> io::Result<Self> {
+        out.write_all(b"GIF89a")?;
+
+        // Logical screen descriptor with a 256 color global table.
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&[0xF7, 0x00, 0x00])?;
+
+        for intensity in 0..=0xFF {
+            out.write_all(&palette.color_for(intensity))?;
+        }
+
+        // NETSCAPE2.0 application extension, so the animation loops forever.
+        out.write_all(&[0x21, 0xFF, 0x0B])?;
+        out.write_all(b"NETSCAPE2.0")?;
+        out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Self { out })
+    }
+
+    pub(crate) fn write_frame(&mut self, width: u16, height: u16, indices: &[u8], delay_cs: u16) -> io::Result<()> {
+        // Graphic control extension, holding the frame delay.
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay_cs.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole screen, without a local color table.
+        self.out.write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00])?;
+        self.out.write_all(&width.to_le_bytes())?;
+        self.out.write_all(&height.to_le_bytes())?;
+        self.out.write_all(&[0x00])?;
+
+        self.out.write_all(&[MIN_CODE_SIZE])?;
+        for sub_block in lzw_compress(indices).chunks(0xFF) {
+            self.out.write_all(&[sub_block.len() as u8])?;
+            self.out.write_all(sub_block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    buffered_bits: u8,
+}
+
+impl BitWriter {
+    fn write_code(&mut self, code: u16, code_size: u8) {
+        self.buffer |= (code as u32) << self.buffered_bits;
+        self.buffered_bits += code_size;
+
+        while self.buffered_bits >= 8 {
+            self.bytes.push((self.buffer & 0xFF) as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.buffered_bits > 0 {
+            self.bytes.push((self.buffer & 0xFF) as u8);
+        }
+
+        self.bytes
+    }
+}
+
+fn lzw_compress(indices: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, buffered_bits: 0 };
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = END_OF_INFORMATION_CODE + 1;
+
+    writer.write_code(CLEAR_CODE, code_size);
+
+    let mut symbols = indices.iter();
+    let mut prefix = match symbols.next() {
+        Some(symbol) => *symbol as u16,
+        None => {
+            writer.write_code(END_OF_INFORMATION_CODE, code_size);
+            return writer.finish();
+        },
+    };
+
+    for symbol in symbols {
+        if let Some(code) = dictionary.get(&(prefix, *symbol)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write_code(prefix, code_size);
+
+        if next_code < MAX_CODE_COUNT {
+            dictionary.insert((prefix, *symbol), next_code);
+            next_code += 1;
+
+            if next_code > (1 << code_size) && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        } else {
+            writer.write_code(CLEAR_CODE, code_size);
+
+            dictionary.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = END_OF_INFORMATION_CODE + 1;
+        }
+
+        prefix = *symbol as u16;
+    }
+
+    writer.write_code(prefix, code_size);
+    writer.write_code(END_OF_INFORMATION_CODE, code_size);
+
+    writer.finish()
+}
+
+// Records the presented frames at 60 Hz. A frame is only written once the next different
+// frame shows up, so runs of identical frames collapse into a single, longer frame. A frame
+// too short for a GIF delay is replaced by the next one, which takes over its time.
+pub(crate) struct GifRecorder<W: Write> {
+    encoder: GifEncoder<W>,
+    pending_frame: Option<Vec<u8>>,
+    pending_frame_count: u64,
+    written_frame_count: u64,
+}
+
+impl<W: Write> GifRecorder<W> {
+    pub(crate) fn new(out: W, palette: &Palette) -> io::Result<Self> {
+        Ok(Self {
+            encoder: GifEncoder::new(out, DISPLAY_WIDTH as u16, DISPLAY_HEIGHT as u16, palette)?,
+            pending_frame: None,
+            pending_frame_count: 0,
+            written_frame_count: 0,
+        })
+    }
+
+    pub(crate) fn capture(&mut self, display: &Display) -> io::Result<()> {
+        if self.pending_frame.as_deref() == Some(&display.data[..]) {
+            self.pending_frame_count += 1;
+            return Ok(());
+        }
+
+        if self.pending_delay_cs() >= MIN_DELAY_CS {
+            self.flush_pending_frame()?;
+        }
+        self.pending_frame = Some(display.data.to_vec());
+        self.pending_frame_count += 1;
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.flush_pending_frame()?;
+
+        self.encoder.finish()
+    }
+
+    fn flush_pending_frame(&mut self) -> io::Result<()> {
+        let Some(frame) = self.pending_frame.take() else {
+            return Ok(());
+        };
+
+        // Only the last frame can still be too short, it is stretched a little.
+        let delay_cs = self.pending_delay_cs().clamp(MIN_DELAY_CS, u16::MAX as u64) as u16;
+        self.written_frame_count += self.pending_frame_count;
+        self.pending_frame_count = 0;
+
+        self.encoder.write_frame(DISPLAY_WIDTH as u16, DISPLAY_HEIGHT as u16, &frame, delay_cs)
+    }
+
+    // GIF delays are in centiseconds, which don't divide a 60 Hz frame evenly.
+    // Rounding the frame boundaries, instead of each delay, keeps the total duration exact.
+    fn pending_delay_cs(&self) -> u64 {
+        to_centiseconds(self.written_frame_count + self.pending_frame_count) - to_centiseconds(self.written_frame_count)
+    }
+}
+
+fn to_centiseconds(frame_count: u64) -> u64 {
+    (frame_count * CENTISECONDS_PER_SECOND + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Walks the blocks of a GIF written by GifEncoder and returns the frame delays.
+    fn frame_delays(gif: &[u8]) -> Vec<u16> {
+        let mut delays = Vec::new();
+        let mut index = 6 + 7 + 256 * 3;
+
+        let skip_sub_blocks = |mut index: usize| {
+            while gif[index] != 0 {
+                index += gif[index] as usize + 1;
+            }
+            index + 1
+        };
+
+        loop {
+            match gif[index] {
+                0x21 => {
+                    if gif[index + 1] == 0xF9 {
+                        delays.push(u16::from_le_bytes([gif[index + 4], gif[index + 5]]));
+                    }
+                    index = skip_sub_blocks(index + 2);
+                },
+                0x2C => {
+                    index = skip_sub_blocks(index + 10 + 1);
+                },
+                0x3B => break,
+                block => panic!("Unexpected block {:#x}.", block),
+            }
+        }
+
+        delays
+    }
+
+    fn record(frames: &[u8]) -> Vec<u16> {
+        let mut recorder = GifRecorder::new(Vec::new(), &Palette::default()).unwrap();
+        let mut display = Display::new();
+        for frame in frames {
+            display.data[0] = *frame;
+            recorder.capture(&display).unwrap();
+        }
+
+        frame_delays(&recorder.finish().unwrap())
+    }
+
+    #[test]
+    fn frames_changing_every_frame_keep_delays_of_at_least_two_centiseconds() {
+        let frames = (0..60).map(|frame| (frame % 2) as u8 * 0xFF).collect::<Vec<_>>();
+        let delays = record(&frames);
+
+        assert!(delays.iter().all(|delay| *delay >= MIN_DELAY_CS as u16), "{:?}", delays);
+        assert_eq!(delays.iter().map(|delay| *delay as u64).sum::<u64>(), 100);
+    }
+
+    #[test]
+    fn identical_frames_collapse_into_one() {
+        assert_eq!(record(&[0xFF; 30]), vec![50]);
+        assert_eq!(record(&[[0xFF; 6], [0x00; 6]].concat()), vec![10, 10]);
+    }
+}
@@ -0,0 +1,192 @@
+// A frontend without any real input or output, for machines with no display or sound card.
+// Presented frames are kept in memory and the buzzer is reduced to the time intervals in
+// which it would have been on, so both can be checked after a run.
+
+use std::collections::VecDeque;
+
+use crate::audio::{SoundEvent, SoundRegisters};
+use crate::display::{Display, Palette};
+use crate::frontend::Frontend;
+use crate::Event;
+
+// Only the most recent frames are kept, so long runs don't grow without bound.
+const MAX_RECORDED_FRAMES: usize = 60 * 60;
+
+pub(crate) struct MemoryVideoSink {
+    frames: VecDeque<Display>,
+    frame_count: u64,
+}
+
+impl MemoryVideoSink {
+    fn record(&mut self, display: &Display) {
+        if self.frames.len() == MAX_RECORDED_FRAMES {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(display.clone());
+        self.frame_count += 1;
+    }
+
+    pub(crate) fn frames(&self) -> &VecDeque<Display> {
+        &self.frames
+    }
+
+    pub(crate) fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+}
+
+pub(crate) struct NullAudioSink {
+    // Emulated time at the start of the next frame, in seconds.
+    time: f64,
+    is_muted: bool,
+    // When the buzzer was on, as (start, end) times in seconds.
+    intervals: Vec<(f64, f64)>,
+}
+
+impl NullAudioSink {
+    fn queue(&mut self, events: &[SoundEvent], clocks_per_frame: u32, frame_duration: f64) {
+        for event in events {
+            let start = self.time + event.clock as f64 / clocks_per_frame as f64 * frame_duration;
+            let end = start + event.duration as f64 * frame_duration;
+
+            // Writing to the sound timer overrides the end of the current tone.
+            if let Some(last_interval) = self.intervals.last_mut() {
+                if last_interval.1 > start {
+                    last_interval.1 = start;
+                }
+            }
+
+            if event.duration > 0 && !self.is_muted {
+                match self.intervals.last_mut() {
+                    Some(last_interval) if last_interval.1 == start => last_interval.1 = end,
+                    _ => self.intervals.push((start, end)),
+                }
+            }
+        }
+
+        self.time += frame_duration;
+    }
+
+    pub(crate) fn intervals(&self) -> &[(f64, f64)] {
+        &self.intervals
+    }
+}
+
+pub(crate) struct HeadlessFrontend {
+    pub(crate) video: MemoryVideoSink,
+    pub(crate) audio: NullAudioSink,
+}
+
+pub(crate) fn init_headless() -> HeadlessFrontend {
+    HeadlessFrontend {
+        video: MemoryVideoSink { frames: VecDeque::new(), frame_count: 0 },
+        audio: NullAudioSink { time: 0.0, is_muted: false, intervals: Vec::new() },
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn handle_event_loop(&mut self) -> Vec<Event> {
+        Vec::new()
+    }
+
+    fn update_display(&mut self, display: &Display, _palette: &Palette) {
+        self.video.record(display);
+    }
+
+    fn queue_sound_events(&mut self, events: &[SoundEvent], clocks_per_frame: u32, frame_duration: f64) {
+        self.audio.queue(events, clocks_per_frame, frame_duration);
+    }
+
+    fn set_muted(&mut self, is_muted: bool) {
+        self.audio.is_muted = is_muted;
+    }
+
+    fn set_sound_registers(&mut self, _registers: SoundRegisters) { }
+
+    // Nobody is watching, so there is no reason to run in real time.
+    fn is_realtime(&self) -> bool {
+        false
+    }
+
+    // Prints what the run produced, so scripts can check it.
+    fn finish(&mut self) {
+        println!("frames: {}", self.video.frame_count());
+        if let Some(last_frame) = self.video.frames().back() {
+            println!("last frame fingerprint: {:016x}", last_frame.fingerprint());
+        }
+
+        for (start, end) in self.audio.intervals() {
+            println!("buzzer: {:.4}s - {:.4}s", start, end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::quirks::Quirks;
+    use crate::{held_keys, FRAME_DURATION};
+
+    const INSTRUCTIONS_PER_FRAME: u32 = 16;
+
+    // Runs frames the way the main loop does at normal speed.
+    fn run_rom(rom: &[u8], frame_count: u64) -> HeadlessFrontend {
+        let mut frontend = init_headless();
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom.to_vec(), Quirks::default());
+
+        for _ in 0..frame_count {
+            cpu.display.fade_pixels();
+            for _ in 0..INSTRUCTIONS_PER_FRAME {
+                cpu.tick(held_keys([false; 16]));
+            }
+            cpu.decrement_timers();
+            frontend.queue_sound_events(&cpu.take_sound_events(), INSTRUCTIONS_PER_FRAME, FRAME_DURATION);
+            frontend.update_display(&cpu.display, &Palette::default());
+        }
+
+        frontend
+    }
+
+    #[test]
+    fn records_frames_and_buzzer_intervals() {
+        // V0 = 30, sound timer = V0, then loop forever.
+        let frontend = run_rom(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04], 60);
+
+        assert_eq!(frontend.video.frame_count(), 60);
+        assert_eq!(frontend.video.frames().len(), 60);
+
+        // The sound timer is written by the second instruction of the first frame.
+        let start = 1.0 / INSTRUCTIONS_PER_FRAME as f64 * FRAME_DURATION;
+        let intervals = frontend.audio.intervals();
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].0 - start).abs() < 1e-9);
+        assert!((intervals[0].1 - (start + 30.0 * FRAME_DURATION)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rewriting_the_sound_timer_cuts_the_tone() {
+        // Sound timer = 30, wait for the delay timer set to 6, sound timer = 0, loop forever.
+        let rom = [
+            0x60, 0x1E, 0xF0, 0x18, 0x61, 0x06, 0xF1, 0x15,
+            0xF2, 0x07, 0x32, 0x00, 0x12, 0x08,
+            0x60, 0x00, 0xF0, 0x18, 0x12, 0x12,
+        ];
+        let frontend = run_rom(&rom, 30);
+
+        let intervals = frontend.audio.intervals();
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].1 - intervals[0].0 < 7.0 * FRAME_DURATION);
+        assert!(intervals[0].1 - intervals[0].0 > 5.0 * FRAME_DURATION);
+    }
+
+    #[test]
+    fn only_the_latest_frames_are_kept() {
+        let frontend = run_rom(&[0x12, 0x00], MAX_RECORDED_FRAMES as u64 + 10);
+
+        assert_eq!(frontend.video.frame_count(), MAX_RECORDED_FRAMES as u64 + 10);
+        assert_eq!(frontend.video.frames().len(), MAX_RECORDED_FRAMES);
+    }
+}
@@ -34,6 +34,8 @@ pub(crate) enum OpCode {
     SetIndexRegisterToFont,
     SetDelayRegister,
     SetSoundRegister,
+    SetPitchRegister,
+    LoadAudioPattern,
     ReadDelayRegister,
     SkipIfKeyPressed,
     SkipIfKeyNotPressed,
@@ -79,6 +81,10 @@ impl From<u16> for OpCode {
         if value == 0x00EE {
             return Self::ReturnFromSubrotine;
         }
+
+        if value == 0xF002 {
+            return Self::LoadAudioPattern;
+        }
         let higher_nibble = value >> 12;
         let lower_nibble = value & 0xF;
         let lower_byte = value & 0xFF;
@@ -118,6 +124,7 @@ impl From<u16> for OpCode {
                     0x1E => { return Self::AddToIndexRegister },
                     0x29 => { return Self::SetIndexRegisterToFont },
                     0x33 => { return Self::DecodeBCD },
+                    0x3A => { return Self::SetPitchRegister },
                     0x55 => { return Self::SaveRegisters },
                     0x65 => { return Self::LoadRegisters },
                     _ => { }
@@ -0,0 +1,182 @@
+// Maps host keys to the 16 CHIP-8 keys. Host keys are identified by name, the same way in
+// every frontend ("w", "up", "keypad 8"...), and several host keys can press the same
+// CHIP-8 key. The bindings come from the [keys] section of the config file:
+//
+//     [keys]
+//     preset = azerty
+//     5 = z, up
+//
+// A preset replaces every binding, each hex digit replaces the bindings of that CHIP-8 key.
+// A [keys.<ROM file name>] section is applied on top, for games with their own controls.
+//
+// Game controllers are named "pad1" and "pad2", in the order they were plugged in, so a
+// two-player game can give each controller its half of the keypad:
+//
+//     [keys.PONG]
+//     1 = 1, pad1 dpup
+//     4 = q, pad1 dpdown
+//     c = 4, pad2 dpup
+//     d = r, pad2 dpdown
+//
+// Controller inputs are the SDL button names ("a", "start", "dpleft"...), "left stick up"
+// and the other stick directions, and "left trigger" / "right trigger".
+//
+// Tab is held to fast-forward in every frontend, so it can't be bound.
+
+use crate::config::Config;
+
+const KEY_COUNT: usize = 16;
+const RESERVED_KEY_NAMES: [&str; 1] = ["tab"];
+
+// Every preset lets the first controller play games that move with 2/4/6/8 and act with 5.
+const DEFAULT_PAD_BINDINGS: [(u8, &str); 5] = [
+    (0x2, "pad1 dpup, pad1 left stick up"),
+    (0x4, "pad1 dpleft, pad1 left stick left"),
+    (0x6, "pad1 dpright, pad1 left stick right"),
+    (0x8, "pad1 dpdown, pad1 left stick down"),
+    (0x5, "pad1 a"),
+];
+
+// The CHIP-8 keypad, as laid out on the COSMAC VIP.
+const KEYPAD_LAYOUT: [u8; KEY_COUNT] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+#[derive(Clone)]
+pub(crate) struct Keymap {
+    // The host keys of each CHIP-8 key, as normalized names.
+    bindings: [Vec<String>; KEY_COUNT],
+}
+
+impl Keymap {
+    pub(crate) fn from_preset_name(name: &str) -> Option<Self> {
+        // Host keys in the same order as KEYPAD_LAYOUT, a key can have several names.
+        let host_keys: [&str; KEY_COUNT] = match name {
+            "qwerty" => [
+                "1", "2", "3", "4",
+                "q", "w", "e", "r",
+                "a", "s", "d", "f",
+                "z", "x", "c", "v",
+            ],
+            // The number row also answers to its unshifted symbols.
+            "azerty" => [
+                "1, &", "2, é", "3, \"", "4, '",
+                "a", "z", "e", "r",
+                "q", "s", "d", "f",
+                "w", "x", "c", "v",
+            ],
+            "numpad" => [
+                "keypad 7", "keypad 8", "keypad 9", "keypad /",
+                "keypad 4", "keypad 5", "keypad 6", "keypad *",
+                "keypad 1", "keypad 2", "keypad 3", "keypad -",
+                "keypad 0", "keypad .", "keypad enter", "keypad +",
+            ],
+            // Every key is bound to the hex digit printed on it.
+            "vip" => [
+                "1", "2", "3", "c",
+                "4", "5", "6", "d",
+                "7", "8", "9", "e",
+                "a", "0", "b", "f",
+            ],
+            _ => return None,
+        };
+
+        let mut keymap = Self { bindings: Default::default() };
+        for (key_num, names) in KEYPAD_LAYOUT.iter().zip(host_keys) {
+            keymap.bind(*key_num, names);
+        }
+
+        for (key_num, names) in DEFAULT_PAD_BINDINGS {
+            let keyboard_names = keymap.bindings[key_num as usize].join(",");
+            keymap.bind(key_num, &format!("{},{}", keyboard_names, names));
+        }
+
+        Some(keymap)
+    }
+
+    pub(crate) fn from_config(config: &Config, rom_file_name: &str) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        keymap.apply_section(config, "keys")?;
+        keymap.apply_section(config, &format!("keys.{}", rom_file_name))?;
+
+        Ok(keymap)
+    }
+
+    fn apply_section(&mut self, config: &Config, section: &str) -> Result<(), String> {
+        if let Some(preset_name) = config.get(section, "preset") {
+            *self = Self::from_preset_name(preset_name)
+                .ok_or_else(|| format!("Unknown key preset '{}' in section [{}].", preset_name, section))?;
+        }
+
+        for key in config.keys(section) {
+            if key == "preset" {
+                continue;
+            }
+
+            let key_num = u8::from_str_radix(key, 16).ok()
+                .filter(|key_num| (*key_num as usize) < KEY_COUNT)
+                .ok_or_else(|| format!("Unknown CHIP-8 key '{}' in section [{}].", key, section))?;
+            let names = config.get(section, key).unwrap_or("");
+            if let Some(name) = names.split(',').find(|name| RESERVED_KEY_NAMES.contains(&normalize_key_name(name).as_str())) {
+                return Err(format!("The key '{}' in section [{}] is reserved and can't be bound.", name.trim(), section));
+            }
+            self.bind(key_num, names);
+        }
+
+        Ok(())
+    }
+
+    // Replaces the host keys of a CHIP-8 key with a comma separated list of names.
+    fn bind(&mut self, key_num: u8, names: &str) {
+        self.bindings[key_num as usize] = names
+            .split(',')
+            .map(normalize_key_name)
+            .filter(|name| !name.is_empty())
+            .collect();
+    }
+
+    // The CHIP-8 keys pressed by a host key.
+    pub(crate) fn key_nums(&self, host_key_name: &str) -> Vec<u8> {
+        let host_key_name = normalize_key_name(host_key_name);
+
+        (0..KEY_COUNT as u8)
+            .filter(|key_num| self.bindings[*key_num as usize].contains(&host_key_name))
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_preset_name("qwerty").unwrap()
+    }
+}
+
+// Key names are compared ignoring case and spaces, so "Keypad 8" and "keypad8" match.
+fn normalize_key_name(name: &str) -> String {
+    name.chars()
+        .filter(|name_char| !name_char.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap_from(text: &str) -> Result<Keymap, String> {
+        Keymap::from_config(&Config::parse(text).unwrap(), "GAME")
+    }
+
+    #[test]
+    fn tab_can_not_be_bound() {
+        assert!(keymap_from("[keys]\n5 = space, return").is_ok());
+        assert_eq!(
+            keymap_from("[keys]\n5 = space, Tab").err().as_deref(),
+            Some("The key 'Tab' in section [keys] is reserved and can't be bound.")
+        );
+        assert!(keymap_from("[keys.GAME]\na = tab").is_err());
+    }
+}
@@ -1,54 +1,224 @@
+mod audio;
+mod browser;
+mod cli;
+mod config;
 mod cpu;
+#[cfg(any(feature = "sdl", test))]
+mod crt;
 mod instruction;
+mod keymap;
 mod display;
+mod export;
+mod frontend;
+mod gif;
+mod headless;
+mod movie;
+mod png;
+mod quirks;
+mod scheduler;
+mod speed;
+#[cfg(feature = "sdl")]
+mod osd;
+#[cfg(feature = "sdl")]
 mod sdl_wrapper;
+#[cfg(unix)]
+mod terminal;
+mod wav;
+mod watcher;
+mod y4m;
 
-extern crate sdl2;
-
+use std::collections::HashSet;
 use std::env;
-use std::{fs::File, io::Read};
-use std::time::Duration;
+use std::fs;
+use std::process;
+use std::path::Path;
+use std::{fs::File, io::BufWriter, io::Read};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use audio::{BuzzerSettings, SoundRegisters};
+use cli::{Command, FrontendKind, Options};
+use config::Config;
+use display::Palette;
+use export::ExportSettings;
+use frontend::Frontend;
+use gif::GifRecorder;
+use keymap::Keymap;
+use movie::Movie;
+use quirks::Quirks;
+use scheduler::FrameScheduler;
+use speed::FastForward;
+use watcher::RomWatcher;
 
-const CLOCKS_PER_FRAME: u32 = 16;
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 16;
+// The steps the instructions per frame hotkeys go through.
+const INSTRUCTIONS_PER_FRAME_STEPS: [u32; 14] = [1, 2, 4, 8, 10, 12, 15, 20, 30, 50, 100, 200, 500, 1000];
+const FRAME_DURATION: f64 = 1.0 / 60.0;
+// Emulation speeds, relative to real time, that the speed hotkeys step through.
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED_INDEX: usize = 2;
+// The part of a real frame that uncapped fast-forward spends emulating, the rest is for presenting.
+const UNCAPPED_FRAME_TIME: f64 = 0.8;
 
 #[derive(PartialEq)]
 enum Event {
     Quit,
     KeyPressed(u8),
     KeyReleased(u8),
+    Screenshot,
+    ToggleRecording,
+    ToggleMute,
+
+    // Emulator controls, handled here instead of being sent to the CHIP-8 program.
+    TogglePause,
+    // Runs until the end of the current frame, then pauses.
+    AdvanceFrame,
+    // Runs a single instruction, then pauses.
+    StepInstruction,
+    // Restarts the program, keeping the memory.
+    SoftReset,
+    // Reloads the ROM into a fresh machine.
+    HardReset,
+    // Savestates go to "<ROM path>.state".
+    SaveState,
+    LoadState,
+    SpeedUp,
+    SpeedDown,
+    IncreaseInstructionsPerFrame,
+    DecreaseInstructionsPerFrame,
+    // Resets the machine and loads the ROM at the given path.
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+    LoadRom(String),
+    // The emulation runs at the fast-forward speed while the key is held.
+    StartFastForward,
+    StopFastForward,
+
+    // Sent instead of the keypad keys while a menu is shown.
+    MenuUp,
+    MenuDown,
+    MenuSelect,
+    MenuBackspace,
+    MenuText(String),
 }
 
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("Run with --help to see the options.");
+            process::exit(2);
+        },
+    };
+
+    let result = match &options.command {
+        Command::Help => {
+            print!("{}", cli::HELP);
+            Ok(())
+        },
+        Command::Export { movie_path, frame_count, video_path, audio_path } => run_export(&options, movie_path, *frame_count, video_path, audio_path),
+        Command::Fingerprint { frame_count, movie_path } => run_fingerprint(&options, *frame_count, movie_path.as_deref()),
+        Command::Run => run(&options),
+    };
+
+    if let Err(error) = result {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
     let mut keys_held = [false; 16];
     let mut is_running = true;
-    let mut buzzer_on = false;
+    let mut is_muted = false;
+    let mut is_paused = false;
+    let mut is_advancing_frame = false;
+    let mut is_stepping = false;
+    let mut speed_index = NORMAL_SPEED_INDEX;
+    let mut is_fast_forwarding = false;
+    let mut is_audio_muted = false;
+    let mut recorder: Option<GifRecorder<BufWriter<File>>> = None;
 
-    let (audio_device, mut canvas, mut event_pump) = sdl_wrapper::init_sdl();
+    let config = load_config(options)?;
+    let buzzer_settings = BuzzerSettings::from_config(&config)?;
+    let fast_forward = FastForward::from_config(&config)?;
+    let quirks = resolve_quirks(options, &config)?;
+    let database = browser::load_database(&config)?;
+    let palette = config.get_parsed::<Palette>("display", "palette")?.unwrap_or_default();
+    let pixel_scale = config.get_parsed::<u32>("display", "scale")?;
+    if pixel_scale == Some(0) {
+        return Err("The scale must be at least 1.".to_string());
+    }
 
-    let args = env::args().collect::<Vec<_>>();
+    // A keymap file replaces the [keys] sections of the config file.
+    let keymap_config = match &options.keymap_path {
+        Some(keymap_path) => Some(Config::load(keymap_path, true)?),
+        None => None,
+    };
+    let keymap_config = keymap_config.as_ref().unwrap_or(&config);
 
-    let rom_path = args.get(1).unwrap_or_else(|| panic!("Usage: {} <ROM path>.", args[0]));
+    let movie = match &options.play_movie_path {
+        Some(movie_path) => Some(read_movie(movie_path)?),
+        None => None,
+    };
+    let mut recorded_movie = options.record_movie_path.as_ref().map(|_| Movie::default());
 
-    let mut rom = Vec::new();
-    let mut rom_file = File::open(rom_path).expect("Unable to open ROM file.");
-    rom_file.read_to_end(&mut rom).expect("Unable to read ROM file.");
+    // Files given on the command line are read before any window opens.
+    let given_rom = options.rom_path.as_deref().map(read_rom).transpose()?;
+    let state = match &options.load_state_path {
+        Some(state_path) => Some(fs::read(state_path).map_err(|error| format!("Unable to read savestate {}: {}", state_path, error))?),
+        None => None,
+    };
 
-    let mut cpu = cpu::Cpu::new();
-    cpu.load_rom(&rom, false);
+    let keymap = Keymap::from_config(keymap_config, &rom_file_name(options.rom_path.as_deref().unwrap_or("")))?;
+    let mut frontend = init_frontend(options.frontend_kind, &config, buzzer_settings, keymap, pixel_scale)?;
+    let is_realtime = frontend.is_realtime();
+
+    // Without a ROM, one is picked from a menu.
+    let (mut rom_path, mut rom) = match (&options.rom_path, given_rom) {
+        (Some(rom_path), Some(rom)) => (rom_path.clone(), rom),
+        _ => match browser::choose_rom(&config, &database, frontend.as_mut()) {
+            Some(rom_path) => {
+                frontend.set_keymap(Keymap::from_config(keymap_config, &rom_file_name(&rom_path))?);
+                let rom = read_rom(&rom_path)?;
+                (rom_path, rom)
+            },
+            None => return Ok(()),
+        },
+    };
+    let mut rom_watcher = RomWatcher::new(&rom_path);
+    let mut instructions_per_frame = resolve_instructions_per_frame(&config, &database, Some(&rom_path), options.instructions_per_frame)?;
+    let mut scheduler = FrameScheduler::new(Duration::from_secs_f64(FRAME_DURATION), frontend.is_vsynced());
+
+    let mut cpu = new_cpu(&rom, quirks, options);
+    if let (Some(state_path), Some(state)) = (&options.load_state_path, state) {
+        cpu.load_state(&state).map_err(|error| format!("{}: {}", state_path, error))?;
+    }
+
+    let mut sound_registers = SoundRegisters::new();
 
     let mut clock_counter = 0u32;
+    let mut frame_count = 0u64;
+    let mut frame_budget = 0.0f64;
+    let mut rom_to_load: Option<String> = None;
+    // Presented frames and executed instructions since the counters were last updated.
+    let mut counter_start = Instant::now();
+    let mut counted_frames = 0u64;
+    let mut counted_instructions = 0u64;
+    let mut status = String::new();
     loop {
-        if !is_running {
+        if !is_running || Some(frame_count) == options.frame_limit {
             break;
         }
 
-        if buzzer_on {
-            audio_device.resume();
-        } else {
-            audio_device.pause();
+        let current_sound_registers = cpu.sound_registers();
+        if current_sound_registers != sound_registers {
+            frontend.set_sound_registers(current_sound_registers);
+            sound_registers = current_sound_registers;
         }
 
-        let events = sdl_wrapper::handle_event_loop(&mut event_pump);
+        let events = frontend.handle_event_loop();
 
         if events.contains(&Event::Quit) {
             is_running = false;
@@ -65,29 +235,490 @@ fn main() {
                 Event::KeyReleased(key_num) => {
                     keys_held[key_num as usize] = false;
                 },
+                Event::Screenshot => {
+                    save_screenshot(&cpu.display, &palette, frontend.as_mut());
+                },
+                Event::ToggleMute => {
+                    is_muted = !is_muted;
+                    frontend.show_message(if is_muted { "Sound off" } else { "Sound on" });
+                },
+                Event::TogglePause => {
+                    is_paused = !is_paused;
+                },
+                Event::AdvanceFrame => {
+                    is_paused = true;
+                    is_advancing_frame = true;
+                },
+                Event::StepInstruction => {
+                    is_paused = true;
+                    is_stepping = true;
+                },
+                Event::SoftReset => {
+                    cpu.reset();
+                    clock_counter = 0;
+                    frontend.show_message("Soft reset");
+                },
+                Event::HardReset => {
+                    cpu = new_cpu(&rom, quirks, options);
+                    clock_counter = 0;
+                    frontend.show_message("Hard reset");
+                },
+                Event::SaveState => {
+                    save_state(&cpu, &rom_path, frontend.as_mut());
+                },
+                Event::LoadState => {
+                    if load_state(&mut cpu, &rom_path, frontend.as_mut()) {
+                        clock_counter = 0;
+                    }
+                },
+                Event::SpeedUp => {
+                    speed_index = (speed_index + 1).min(SPEEDS.len() - 1);
+                    frontend.show_message(&format!("Speed {}x", SPEEDS[speed_index]));
+                },
+                Event::SpeedDown => {
+                    speed_index = speed_index.saturating_sub(1);
+                    frontend.show_message(&format!("Speed {}x", SPEEDS[speed_index]));
+                },
+                Event::IncreaseInstructionsPerFrame => {
+                    instructions_per_frame = INSTRUCTIONS_PER_FRAME_STEPS.into_iter()
+                        .find(|step| *step > instructions_per_frame)
+                        .unwrap_or(instructions_per_frame);
+                    frontend.show_message(&format!("{} instructions/frame", instructions_per_frame));
+                },
+                Event::DecreaseInstructionsPerFrame => {
+                    instructions_per_frame = INSTRUCTIONS_PER_FRAME_STEPS.into_iter()
+                        .rev()
+                        .find(|step| *step < instructions_per_frame)
+                        .unwrap_or(instructions_per_frame);
+                    frontend.show_message(&format!("{} instructions/frame", instructions_per_frame));
+                },
+                Event::LoadRom(path) => {
+                    rom_to_load = Some(path);
+                },
+                Event::MenuUp | Event::MenuDown | Event::MenuSelect | Event::MenuBackspace | Event::MenuText(_) => { },
+                Event::StartFastForward => {
+                    is_fast_forwarding = true;
+                },
+                Event::StopFastForward => {
+                    is_fast_forwarding = false;
+                },
+                Event::ToggleRecording => {
+                    recorder = match recorder.take() {
+                        Some(active_recorder) => {
+                            finish_recording(active_recorder, frontend.as_mut());
+                            None
+                        },
+                        None => start_recording(&palette, frontend.as_mut()),
+                    };
+                },
             }
         }
 
-        cpu.display.fade_pixels();
+        if rom_watcher.has_changed() {
+            rom_to_load = Some(rom_path.clone());
+        }
 
-        let keys = keys_held
-            .into_iter()
-            .enumerate()
-            .filter_map(|(index, is_held)| if is_held { Some(index as u8) } else { None })
-            .collect();
+        if let Some(path) = rom_to_load.take() {
+            match read_rom(&path) {
+                Ok(new_rom) => {
+                    rom = new_rom;
+                    cpu = new_cpu(&rom, quirks, options);
+                    clock_counter = 0;
+
+                    // A different ROM comes with its own settings.
+                    if path != rom_path {
+                        match Keymap::from_config(keymap_config, &rom_file_name(&path)) {
+                            Ok(keymap) => frontend.set_keymap(keymap),
+                            Err(error) => report_error(frontend.as_mut(), &error),
+                        }
+                        match resolve_instructions_per_frame(&config, &database, Some(&path), options.instructions_per_frame) {
+                            Ok(value) => instructions_per_frame = value,
+                            Err(error) => report_error(frontend.as_mut(), &error),
+                        }
+                        rom_watcher = RomWatcher::new(&path);
+                        rom_path = path;
+                    }
+
+                    frontend.show_message(&format!("Loaded {}", rom_file_name(&rom_path)));
+                },
+                Err(error) => report_error(frontend.as_mut(), &error),
+            }
+        }
+
+        let speed = match (is_fast_forwarding, fast_forward) {
+            (true, FastForward::Speed(fast_forward_speed)) => Some(fast_forward_speed),
+            (true, FastForward::Uncapped) => None,
+            (false, _) => Some(SPEEDS[speed_index]),
+        };
+
+        // Without a known speed, there is no telling how long the sounds should last.
+        let should_mute = is_muted || is_paused || speed.is_none();
+        if should_mute != is_audio_muted {
+            frontend.set_muted(should_mute);
+            is_audio_muted = should_mute;
+        }
+
+        let current_status = format_status(instructions_per_frame, speed, is_paused, scheduler.dropped_frames());
+        if current_status != status {
+            frontend.set_status(&current_status);
+            frontend.set_paused(is_paused);
+            status = current_status;
+        }
+
+        if is_stepping {
+            is_stepping = false;
+
+            let frame_keys = keys_for_frame(keys_held, movie.as_ref(), recorded_movie.as_mut(), frame_count);
+            cpu.tick(held_keys(frame_keys));
+            clock_counter += 1;
+            counted_instructions += 1;
+        } else if !is_paused || is_advancing_frame {
+            // Every real frame runs as many emulated frames as the speed asks for. In slow
+            // motion, an emulated frame only runs every few real frames. Uncapped, frames run
+            // until most of the real frame is used up.
+            let real_frame_start = Instant::now();
+            let is_uncapped = speed.is_none() && !is_advancing_frame;
+            frame_budget += match speed {
+                _ if is_advancing_frame => 1.0,
+                Some(speed) => speed,
+                None => 1.0,
+            };
+            is_advancing_frame = false;
 
-        cpu.tick(keys);
+            // The fade follows the presented frames, so it looks the same at any speed.
+            if frame_budget >= 1.0 {
+                cpu.display.fade_pixels();
+            }
+
+            while (frame_budget >= 1.0 || is_uncapped) && Some(frame_count) != options.frame_limit {
+                if is_uncapped && real_frame_start.elapsed().as_secs_f64() > FRAME_DURATION * UNCAPPED_FRAME_TIME {
+                    break;
+                }
+                frame_budget = (frame_budget - 1.0).max(0.0);
+
+                // The rate can be lowered in the middle of a frame, which then ends right away.
+                let frame_keys = keys_for_frame(keys_held, movie.as_ref(), recorded_movie.as_mut(), frame_count);
+                while clock_counter < instructions_per_frame {
+                    cpu.tick(held_keys(frame_keys));
+                    clock_counter += 1;
+                    counted_instructions += 1;
+                }
+
+                if let Some(active_recorder) = &mut recorder {
+                    if let Err(error) = active_recorder.capture(&cpu.display) {
+                        report_error(frontend.as_mut(), &format!("Unable to record frame, stopping the recording: {}", error));
+                        recorder = None;
+                    }
+                }
+                cpu.decrement_timers();
+                let sound_events = cpu.take_sound_events();
+                if let Some(speed) = speed {
+                    frontend.queue_sound_events(&sound_events, clock_counter, FRAME_DURATION / speed);
+                }
+
+                clock_counter = 0;
+                frame_count += 1;
+            }
+        }
+
+        // Presented even when nothing ran, so the overlay stays up to date. A step shows
+        // its result right away, instead of at the end of the frame.
+        frontend.update_display(&cpu.display, &palette);
+        counted_frames += 1;
+
+        let counter_time = counter_start.elapsed().as_secs_f64();
+        if counter_time >= 1.0 {
+            frontend.set_counters(counted_frames as f64 / counter_time, counted_instructions as f64 / counter_time);
+            counter_start = Instant::now();
+            counted_frames = 0;
+            counted_instructions = 0;
+        }
 
-        clock_counter += 1;
-        if clock_counter == CLOCKS_PER_FRAME { // NEW FRAME
-            sdl_wrapper::update_display(&mut canvas, &cpu.display);
-            buzzer_on = cpu.decrement_timers();
+        // Nothing is owed to a paused emulation.
+        if is_paused {
+            frame_budget = 0.0;
+        }
 
-            clock_counter = 0;
+        if is_realtime {
+            scheduler.finish_frame();
         }
+    }
+
+    if let Some(active_recorder) = recorder {
+        finish_recording(active_recorder, frontend.as_mut());
+    }
+    frontend.finish();
+
+    // Closes the window or gives the terminal back before printing.
+    drop(frontend);
+    if scheduler.dropped_frames() > 0 {
+        eprintln!("{} frames were dropped.", scheduler.dropped_frames());
+    }
+
+    if let (Some(movie_path), Some(recorded_movie)) = (&options.record_movie_path, recorded_movie) {
+        fs::write(movie_path, recorded_movie.to_text()).map_err(|error| format!("Unable to save movie to {}: {}", movie_path, error))?;
+        println!("Movie saved to {}.", movie_path);
+    }
+
+    Ok(())
+}
 
-        // TODO: Actually we need to figure out how much time we used in the current clock cicle
-        // and wait only the amount of time until the next clock.
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / (60 * CLOCKS_PER_FRAME)));
+// The profile picked on the command line, changed by the [quirks] section.
+fn resolve_quirks(options: &Options, config: &Config) -> Result<Quirks, String> {
+    let mut quirks = options.quirks;
+    quirks.apply_config(config)?;
+
+    Ok(quirks)
+}
+
+fn load_config(options: &Options) -> Result<Config, String> {
+    let config_path = options.config_path.as_deref().unwrap_or(config::DEFAULT_CONFIG_PATH);
+    let mut config = Config::load(config_path, options.config_path.is_some())?;
+
+    for (section, key, value) in &options.config_overrides {
+        config.set(section, key, value);
+    }
+
+    Ok(config)
+}
+
+fn init_frontend(kind: FrontendKind, config: &Config, buzzer_settings: BuzzerSettings, keymap: Keymap, pixel_scale: Option<u32>) -> Result<Box<dyn Frontend>, String> {
+    match kind {
+        FrontendKind::Sdl => init_sdl_frontend(config, buzzer_settings, keymap, pixel_scale),
+        FrontendKind::Terminal => init_terminal_frontend(keymap),
+        FrontendKind::Headless => Ok(Box::new(headless::init_headless())),
     }
 }
+
+#[cfg(feature = "sdl")]
+fn init_sdl_frontend(config: &Config, buzzer_settings: BuzzerSettings, keymap: Keymap, pixel_scale: Option<u32>) -> Result<Box<dyn Frontend>, String> {
+    let crt_settings = crt::CrtSettings::from_config(config)?;
+
+    match sdl_wrapper::init_sdl(crt_settings, buzzer_settings, keymap, pixel_scale) {
+        Ok(frontend) => Ok(Box::new(frontend)),
+        Err(error) => Err(format!("Unable to open a window: {}. Use --terminal or --headless to run without one.", error)),
+    }
+}
+
+// Without SDL, the terminal is the only interactive frontend left.
+#[cfg(not(feature = "sdl"))]
+fn init_sdl_frontend(_config: &Config, _buzzer_settings: BuzzerSettings, keymap: Keymap, _pixel_scale: Option<u32>) -> Result<Box<dyn Frontend>, String> {
+    init_terminal_frontend(keymap)
+}
+
+#[cfg(unix)]
+fn init_terminal_frontend(keymap: Keymap) -> Result<Box<dyn Frontend>, String> {
+    Ok(Box::new(terminal::init_terminal(keymap)?))
+}
+
+#[cfg(not(unix))]
+fn init_terminal_frontend(_keymap: Keymap) -> Result<Box<dyn Frontend>, String> {
+    Err("The terminal frontend is only available on Unix systems.".to_string())
+}
+
+fn new_cpu(rom: &Vec<u8>, quirks: Quirks, options: &Options) -> cpu::Cpu {
+    let mut cpu = cpu::Cpu::new();
+    cpu.load_rom(rom, quirks);
+    if let Some(seed) = options.seed {
+        cpu.seed_rng(seed);
+    }
+
+    cpu
+}
+
+// A movie being played replaces the keys of the player, a movie being recorded keeps them.
+fn keys_for_frame(keys_held: [bool; 16], movie: Option<&Movie>, recorded_movie: Option<&mut Movie>, frame: u64) -> [bool; 16] {
+    let keys = match movie {
+        Some(movie) => movie.keys_at(frame),
+        None => keys_held,
+    };
+    if let Some(recorded_movie) = recorded_movie {
+        recorded_movie.record(frame, keys);
+    }
+
+    keys
+}
+
+pub(crate) fn held_keys(keys_held: [bool; 16]) -> HashSet<u8> {
+    keys_held
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, is_held)| if is_held { Some(index as u8) } else { None })
+        .collect()
+}
+
+fn read_rom(rom_path: &str) -> Result<Vec<u8>, String> {
+    let mut rom = Vec::new();
+    let mut rom_file = File::open(rom_path).map_err(|error| format!("Unable to open ROM file {}: {}", rom_path, error))?;
+    rom_file.read_to_end(&mut rom).map_err(|error| format!("Unable to read ROM file {}: {}", rom_path, error))?;
+
+    if rom.len() > cpu::MAX_ROM_SIZE {
+        return Err(format!("{} is too big to be a ROM: {} bytes, at most {} fit in memory.", rom_path, rom.len(), cpu::MAX_ROM_SIZE));
+    }
+
+    Ok(rom)
+}
+
+fn read_movie(movie_path: &str) -> Result<Movie, String> {
+    let movie_text = fs::read_to_string(movie_path).map_err(|error| format!("Unable to read movie file {}: {}", movie_path, error))?;
+    Movie::parse(&movie_text).map_err(|error| format!("Invalid movie file {}: {}", movie_path, error))
+}
+
+fn rom_file_name(rom_path: &str) -> String {
+    Path::new(rom_path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// The command line wins over the [cpu.<ROM file name>] section, then comes the speed
+// recommended by the ROM database, then the [cpu] section.
+fn resolve_instructions_per_frame(config: &Config, database: &Config, rom_path: Option<&str>, option: Option<u32>) -> Result<u32, String> {
+    let rom_file_name = rom_file_name(rom_path.unwrap_or(""));
+    let rom_section = format!("cpu.{}", rom_file_name);
+
+    let value = match option {
+        Some(value) => Some(value),
+        None => [(config, rom_section.as_str()), (database, rom_file_name.as_str()), (config, "cpu")]
+            .into_iter()
+            .map(|(source, section)| source.get_parsed::<u32>(section, "instructions_per_frame"))
+            .find(|value| !matches!(value, Ok(None)))
+            .unwrap_or(Ok(None))?,
+    };
+
+    match value {
+        Some(0) => Err("The instructions per frame must be at least 1.".to_string()),
+        Some(value) => Ok(value),
+        None => Ok(DEFAULT_INSTRUCTIONS_PER_FRAME),
+    }
+}
+
+fn format_status(instructions_per_frame: u32, speed: Option<f64>, is_paused: bool, dropped_frames: u64) -> String {
+    let mut status = format!("{} instructions/frame", instructions_per_frame);
+    match speed {
+        Some(speed) if speed != 1.0 => status.push_str(&format!(", {}x speed", speed)),
+        Some(_) => { },
+        None => status.push_str(", uncapped speed"),
+    }
+    if is_paused {
+        status.push_str(", paused");
+    }
+    if dropped_frames > 0 {
+        status.push_str(&format!(", {} dropped frames", dropped_frames));
+    }
+
+    status
+}
+
+fn run_export(options: &Options, movie_path: &str, frame_count: u64, video_path: &str, audio_path: &str) -> Result<(), String> {
+    let settings = load_export_settings(options)?;
+    let movie = read_movie(movie_path)?;
+
+    export::run_export(&settings, &movie, frame_count, video_path, audio_path)
+        .map_err(|error| format!("Unable to export the video and audio: {}", error))
+}
+
+fn run_fingerprint(options: &Options, frame_count: u64, movie_path: Option<&str>) -> Result<(), String> {
+    let settings = load_export_settings(options)?;
+    let movie = match movie_path {
+        Some(movie_path) => read_movie(movie_path)?,
+        None => Movie::default(),
+    };
+
+    for (frame, fingerprint) in export::run_fingerprints(&settings, &movie, frame_count).into_iter().enumerate() {
+        println!("{} {:016x}", frame, fingerprint);
+    }
+
+    Ok(())
+}
+
+fn load_export_settings(options: &Options) -> Result<ExportSettings, String> {
+    let config = load_config(options)?;
+    let database = browser::load_database(&config)?;
+    let rom_path = options.rom_path.as_deref().unwrap_or("");
+
+    Ok(ExportSettings {
+        rom: read_rom(rom_path)?,
+        quirks: resolve_quirks(options, &config)?,
+        buzzer_settings: BuzzerSettings::from_config(&config)?,
+        instructions_per_frame: resolve_instructions_per_frame(&config, &database, Some(rom_path), options.instructions_per_frame)?,
+        seed: options.seed.unwrap_or(export::DEFAULT_SEED),
+    })
+}
+
+fn timestamp_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or(0)
+}
+
+fn save_screenshot(display: &display::Display, palette: &Palette, frontend: &mut dyn Frontend) {
+    let path = format!("screenshot_{}.png", timestamp_millis());
+
+    match std::fs::write(&path, display.to_png(1, palette)) {
+        Ok(()) => report(frontend, &format!("Screenshot saved to {}.", path)),
+        Err(error) => report_error(frontend, &format!("Unable to save screenshot to {}: {}", path, error)),
+    }
+}
+
+// Savestates sit next to the ROM, one per ROM.
+fn state_path(rom_path: &str) -> String {
+    format!("{}.state", rom_path)
+}
+
+fn save_state(cpu: &cpu::Cpu, rom_path: &str, frontend: &mut dyn Frontend) {
+    let path = state_path(rom_path);
+
+    match fs::write(&path, cpu.save_state()) {
+        Ok(()) => report(frontend, &format!("State saved to {}.", path)),
+        Err(error) => report_error(frontend, &format!("Unable to save state to {}: {}", path, error)),
+    }
+}
+
+// Returns whether the state was loaded, the machine is left untouched otherwise.
+fn load_state(cpu: &mut cpu::Cpu, rom_path: &str, frontend: &mut dyn Frontend) -> bool {
+    let path = state_path(rom_path);
+
+    let loaded = fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|state| cpu.load_state(&state));
+    match loaded {
+        Ok(()) => {
+            report(frontend, &format!("State loaded from {}.", path));
+            true
+        },
+        Err(error) => {
+            report_error(frontend, &format!("Unable to load state from {}: {}", path, error));
+            false
+        },
+    }
+}
+
+fn start_recording(palette: &Palette, frontend: &mut dyn Frontend) -> Option<GifRecorder<BufWriter<File>>> {
+    let path = format!("recording_{}.gif", timestamp_millis());
+
+    let started = File::create(&path).and_then(|file| GifRecorder::new(BufWriter::new(file), palette));
+    match started {
+        Ok(recorder) => {
+            report(frontend, &format!("Recording to {}.", path));
+            Some(recorder)
+        },
+        Err(error) => {
+            report_error(frontend, &format!("Unable to start recording to {}: {}", path, error));
+            None
+        },
+    }
+}
+
+fn finish_recording(recorder: GifRecorder<BufWriter<File>>, frontend: &mut dyn Frontend) {
+    match recorder.finish() {
+        Ok(_) => report(frontend, "Recording finished."),
+        Err(error) => report_error(frontend, &format!("Unable to finish recording: {}", error)),
+    }
+}
+
+// Messages go both to the console and to the frontend, which may be covering the console.
+fn report(frontend: &mut dyn Frontend, message: &str) {
+    println!("{}", message);
+    frontend.show_message(message);
+}
+
+fn report_error(frontend: &mut dyn Frontend, message: &str) {
+    eprintln!("{}", message);
+    frontend.show_message(message);
+}
@@ -0,0 +1,86 @@
+// Input movies are plain text files. Each line holds a frame number followed by the
+// keys held from that frame on, as hex digits ("-" when no key is held):
+//
+//     # frame keys
+//     0   -
+//     120 5
+//     130 46
+//
+// Lines must be sorted by frame number. Anything after a '#' is a comment.
+
+#[derive(Default)]
+pub(crate) struct Movie {
+    changes: Vec<(u64, [bool; 16])>,
+}
+
+impl Movie {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut changes: Vec<(u64, [bool; 16])> = Vec::new();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_num = line_index + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let frame = fields.next()
+                .and_then(|field| field.parse::<u64>().ok())
+                .ok_or_else(|| format!("Line {}: expected a frame number.", line_num))?;
+            let keys_field = fields.next().ok_or_else(|| format!("Line {}: expected the held keys.", line_num))?;
+
+            if fields.next().is_some() {
+                return Err(format!("Line {}: unexpected trailing data.", line_num));
+            }
+
+            if let Some((last_frame, _)) = changes.last() {
+                if frame < *last_frame {
+                    return Err(format!("Line {}: frame {} comes before frame {}.", line_num, frame, last_frame));
+                }
+            }
+
+            let mut keys = [false; 16];
+            if keys_field != "-" {
+                for digit in keys_field.chars() {
+                    let key_num = digit.to_digit(16).ok_or_else(|| format!("Line {}: '{}' is not a key.", line_num, digit))?;
+                    keys[key_num as usize] = true;
+                }
+            }
+
+            changes.push((frame, keys));
+        }
+
+        Ok(Self { changes })
+    }
+
+    pub(crate) fn keys_at(&self, frame: u64) -> [bool; 16] {
+        self.changes
+            .iter()
+            .take_while(|(change_frame, _)| *change_frame <= frame)
+            .last()
+            .map(|(_, keys)| *keys)
+            .unwrap_or([false; 16])
+    }
+
+    // Adds a line when the keys differ from the ones held before, frames must come in order.
+    pub(crate) fn record(&mut self, frame: u64, keys: [bool; 16]) {
+        if self.changes.last().map(|(_, last_keys)| *last_keys) != Some(keys) {
+            self.changes.push((frame, keys));
+        }
+    }
+
+    pub(crate) fn to_text(&self) -> String {
+        let mut text = String::from("# frame keys\n");
+        for (frame, keys) in &self.changes {
+            let held_keys = (0..16)
+                .filter(|key_num| keys[*key_num])
+                .map(|key_num| format!("{:X}", key_num))
+                .collect::<String>();
+
+            text.push_str(&format!("{} {}\n", frame, if held_keys.is_empty() { "-" } else { &held_keys }));
+        }
+
+        text
+    }
+}
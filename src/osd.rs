@@ -0,0 +1,199 @@
+// An overlay drawn over the emulated screen, with a built-in 5x7 bitmap font. It shows
+// short-lived messages, the performance counters and whether the emulation is paused.
+// It is drawn straight to the canvas, so screenshots and recordings don't include it.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+use crate::browser::MenuView;
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+// Space between characters and lines, and around the text, in font pixels.
+const GLYPH_SPACING: u32 = 1;
+const TEXT_PADDING: u32 = 2;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const MAX_MESSAGES: usize = 4;
+// The font is scaled up by one for every this many pixels of window height.
+const PIXELS_PER_FONT_SCALE: u32 = 240;
+
+const TEXT_COLOR: Color = Color::RGB(255, 255, 255);
+const BACKDROP_COLOR: Color = Color::RGBA(0, 0, 0, 160);
+const SELECTED_BACKDROP_COLOR: Color = Color::RGBA(64, 96, 160, 255);
+
+pub(crate) struct Osd {
+    // Messages and when they were shown, oldest first.
+    messages: VecDeque<(String, Instant)>,
+    pub(crate) counters: Option<String>,
+    pub(crate) is_paused: bool,
+}
+
+impl Osd {
+    pub(crate) fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            counters: None,
+            is_paused: false,
+        }
+    }
+
+    pub(crate) fn show_message(&mut self, message: &str) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back((message.to_string(), Instant::now()));
+    }
+
+    pub(crate) fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        self.messages.retain(|(_, shown_at)| shown_at.elapsed() < MESSAGE_DURATION);
+
+        let (output_width, output_height) = canvas.output_size().unwrap_or((1, 1));
+        let scale = (output_height / PIXELS_PER_FONT_SCALE).max(1);
+        let line_height = (GLYPH_HEIGHT + GLYPH_SPACING + 2 * TEXT_PADDING) * scale;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if let Some(counters) = &self.counters {
+            draw_text(canvas, counters, 0, 0, scale, BACKDROP_COLOR);
+        }
+
+        if self.is_paused {
+            let x = output_width as i32 - text_width("PAUSED", scale) as i32;
+            draw_text(canvas, "PAUSED", x, 0, scale, BACKDROP_COLOR);
+        }
+
+        // Newest message at the bottom.
+        let mut y = output_height as i32;
+        for (message, _) in self.messages.iter().rev() {
+            y -= line_height as i32;
+            draw_text(canvas, message, 0, y, scale, BACKDROP_COLOR);
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+// Draws a menu over the whole canvas: the title, the search filter, the items that fit and
+// the details of the selected item at the bottom.
+pub(crate) fn draw_menu(canvas: &mut Canvas<Window>, menu: &MenuView) {
+    let (_, output_height) = canvas.output_size().unwrap_or((1, 1));
+    let scale = (output_height / PIXELS_PER_FONT_SCALE).max(1);
+    let line_height = ((GLYPH_HEIGHT + GLYPH_SPACING + 2 * TEXT_PADDING) * scale) as i32;
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+
+    draw_text(canvas, &menu.title, 0, 0, scale, BACKDROP_COLOR);
+    draw_text(canvas, &format!("Search: {}_", menu.filter), 0, line_height, scale, BACKDROP_COLOR);
+
+    // Two lines on top, a gap and the details at the bottom.
+    let item_lines = (output_height as i32 / line_height - 4 - menu.details.len() as i32).max(1) as usize;
+    let mut y = 3 * line_height;
+    for item_index in menu.visible_range(item_lines) {
+        let backdrop_color = if Some(item_index) == menu.selected { SELECTED_BACKDROP_COLOR } else { BACKDROP_COLOR };
+        draw_text(canvas, &menu.items[item_index], 0, y, scale, backdrop_color);
+        y += line_height;
+    }
+    if menu.items.is_empty() {
+        draw_text(canvas, "No ROM found.", 0, y, scale, BACKDROP_COLOR);
+    }
+
+    let mut y = output_height as i32 - line_height * menu.details.len() as i32;
+    for detail in &menu.details {
+        draw_text(canvas, detail, 0, y, scale, BACKDROP_COLOR);
+        y += line_height;
+    }
+}
+
+fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * (GLYPH_WIDTH + GLYPH_SPACING) + 2 * TEXT_PADDING) * scale
+}
+
+// Draws the text over a dark backdrop, with (x, y) as the top left corner of the backdrop.
+fn draw_text(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, scale: u32, backdrop_color: Color) {
+    let backdrop_height = (GLYPH_HEIGHT + 2 * TEXT_PADDING) * scale;
+    canvas.set_draw_color(backdrop_color);
+    let _ = canvas.fill_rect(Rect::new(x, y, text_width(text, scale), backdrop_height));
+
+    canvas.set_draw_color(TEXT_COLOR);
+    let mut glyph_x = x + (TEXT_PADDING * scale) as i32;
+    let glyph_y = y + (TEXT_PADDING * scale) as i32;
+    for text_char in text.chars() {
+        for (row_index, row) in glyph(text_char).iter().enumerate() {
+            for column_index in 0..GLYPH_WIDTH {
+                if row & (0x10 >> column_index) != 0 {
+                    let pixel_x = glyph_x + (column_index * scale) as i32;
+                    let pixel_y = glyph_y + (row_index as u32 * scale) as i32;
+                    let _ = canvas.fill_rect(Rect::new(pixel_x, pixel_y, scale, scale));
+                }
+            }
+        }
+
+        glyph_x += ((GLYPH_WIDTH + GLYPH_SPACING) * scale) as i32;
+    }
+}
+
+// Rows of the character, top to bottom, with the leftmost pixel in bit 4. Lowercase letters
+// are drawn as uppercase, characters without a glyph as '?'.
+fn glyph(text_char: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match text_char.to_ascii_uppercase() {
+        ' ' => { [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00] },
+        '0' => { [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E] },
+        '1' => { [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E] },
+        '2' => { [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F] },
+        '3' => { [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E] },
+        '4' => { [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02] },
+        '5' => { [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E] },
+        '6' => { [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E] },
+        '7' => { [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08] },
+        '8' => { [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E] },
+        '9' => { [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C] },
+        'A' => { [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11] },
+        'B' => { [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E] },
+        'C' => { [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E] },
+        'D' => { [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C] },
+        'E' => { [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F] },
+        'F' => { [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10] },
+        'G' => { [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F] },
+        'H' => { [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11] },
+        'I' => { [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E] },
+        'J' => { [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C] },
+        'K' => { [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11] },
+        'L' => { [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F] },
+        'M' => { [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11] },
+        'N' => { [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11] },
+        'O' => { [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E] },
+        'P' => { [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10] },
+        'Q' => { [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D] },
+        'R' => { [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11] },
+        'S' => { [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E] },
+        'T' => { [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04] },
+        'U' => { [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E] },
+        'V' => { [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04] },
+        'W' => { [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A] },
+        'X' => { [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11] },
+        'Y' => { [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04] },
+        'Z' => { [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F] },
+        '.' => { [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C] },
+        ',' => { [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08] },
+        ':' => { [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00] },
+        '-' => { [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00] },
+        '+' => { [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00] },
+        '=' => { [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00] },
+        '/' => { [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00] },
+        '%' => { [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03] },
+        '(' => { [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02] },
+        ')' => { [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08] },
+        '!' => { [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04] },
+        '\'' => { [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00] },
+        '_' => { [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F] },
+        _ => { [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04] },
+    }
+}
@@ -0,0 +1,102 @@
+// A minimal PNG encoder. Image data is stored in uncompressed zlib blocks,
+// which keeps the encoder tiny and the output lossless.
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const MAX_STORED_BLOCK_LEN: usize = 0xFFFF;
+
+const COLOR_TYPE_RGB: u8 = 2;
+
+pub(crate) fn encode_rgb(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height * 3) as usize, "Pixel buffer doesn't match the image size.");
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.push(8); // Bit depth
+    header.push(COLOR_TYPE_RGB);
+    header.push(0); // Compression method
+    header.push(0); // Filter method
+    header.push(0); // Interlace method
+
+    // Every scanline is prefixed with its filter type (0 = None).
+    let line_len = (width * 3) as usize;
+    let mut raw_data = Vec::with_capacity((line_len + 1) * height as usize);
+    for line in pixels.chunks(line_len) {
+        raw_data.push(0);
+        raw_data.extend_from_slice(line);
+    }
+
+    let mut result = Vec::new();
+    result.extend_from_slice(&PNG_SIGNATURE);
+    write_chunk(&mut result, b"IHDR", &header);
+    write_chunk(&mut result, b"IDAT", &zlib_stored(&raw_data));
+    write_chunk(&mut result, b"IEND", &[]);
+
+    result
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let crc_start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+
+    let crc = crc32(&out[crc_start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let block_count = data.len().div_ceil(MAX_STORED_BLOCK_LEN).max(1);
+    let mut result = Vec::with_capacity(data.len() + block_count * 5 + 6);
+
+    // CMF/FLG: deflate with a 32K window, no preset dictionary, fastest compression.
+    result.push(0x78);
+    result.push(0x01);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    if blocks.peek().is_none() {
+        result.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let block_len = block.len() as u16;
+
+        result.push(if is_final { 0x01 } else { 0x00 });
+        result.extend_from_slice(&block_len.to_le_bytes());
+        result.extend_from_slice(&(!block_len).to_le_bytes());
+        result.extend_from_slice(block);
+    }
+
+    result.extend_from_slice(&adler32(data).to_be_bytes());
+
+    result
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+
+    (b << 16) | a
+}
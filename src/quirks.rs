@@ -0,0 +1,63 @@
+use crate::config::Config;
+
+#[derive(Clone, Copy)]
+pub(crate) struct Quirks {
+    // Matches the original COSMAC VIP interpreter: shifts operate on VY and
+    // BNNN jumps to NNN + V0.
+    pub(crate) compatibility_mode: bool,
+    // When set, sprites crossing a screen edge wrap around to the opposite edge.
+    // Otherwise the parts outside the screen are clipped.
+    pub(crate) wrap_sprites: bool,
+    // When set, DXYN halts the CPU until the next frame starts, limiting
+    // the program to 60 sprite draws per second.
+    pub(crate) display_wait: bool,
+}
+
+impl Quirks {
+    pub(crate) const fn new() -> Self {
+        Self {
+            compatibility_mode: false,
+            wrap_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    pub(crate) const fn vip() -> Self {
+        Self {
+            compatibility_mode: true,
+            wrap_sprites: false,
+            display_wait: true,
+        }
+    }
+
+    pub(crate) fn from_profile_name(name: &str) -> Option<Self> {
+        match name {
+            "modern" => Some(Self::new()),
+            "vip" => Some(Self::vip()),
+            _ => None,
+        }
+    }
+
+    // The [quirks] section turns single quirks on or off, on top of the profile.
+    pub(crate) fn apply_config(&mut self, config: &Config) -> Result<(), String> {
+        let quirks = [
+            ("compatibility_mode", &mut self.compatibility_mode),
+            ("wrap_sprites", &mut self.wrap_sprites),
+            ("display_wait", &mut self.display_wait),
+        ];
+
+        for (key, quirk) in quirks {
+            if let Some(value) = config.get_parsed::<bool>("quirks", key)? {
+                *quirk = value;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
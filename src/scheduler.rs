@@ -0,0 +1,166 @@
+// Paces the main loop to real frames. Deadlines are absolute, so the time spent emulating
+// and presenting a frame doesn't add up into drift, and a late frame doesn't delay the
+// frames after it.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Sleeping overshoots by up to a millisecond or so, the end of the wait yields instead.
+const SPIN_DURATION: Duration = Duration::from_millis(1);
+
+pub(crate) struct FrameScheduler {
+    frame_duration: Duration,
+    next_deadline: Instant,
+    last_frame_end: Instant,
+    // With vsync, presenting a frame already waits for the display.
+    is_vsynced: bool,
+    dropped_frames: u64,
+}
+
+impl FrameScheduler {
+    pub(crate) fn new(frame_duration: Duration, is_vsynced: bool) -> Self {
+        let now = Instant::now();
+
+        Self {
+            frame_duration,
+            next_deadline: now + frame_duration,
+            last_frame_end: now,
+            is_vsynced,
+            dropped_frames: 0,
+        }
+    }
+
+    // Called once the frame has been presented, waits until the next one should start.
+    pub(crate) fn finish_frame(&mut self) {
+        if let Some(wake_time) = self.advance(Instant::now()) {
+            sleep_until(wake_time);
+        }
+
+        self.last_frame_end = Instant::now();
+    }
+
+    // Accounts for a frame presented at `now`, returning when the next one should start if
+    // that means waiting.
+    fn advance(&mut self, now: Instant) -> Option<Instant> {
+        if self.is_vsynced {
+            let elapsed = now - self.last_frame_end;
+            if elapsed < self.frame_duration / 2 {
+                // The present didn't wait, which happens while the window is hidden.
+                return Some(self.last_frame_end + self.frame_duration);
+            } else if elapsed > self.frame_duration * 3 / 2 {
+                self.dropped_frames += (elapsed.as_secs_f64() / self.frame_duration.as_secs_f64()).round() as u64 - 1;
+            }
+
+            return None;
+        }
+
+        if now < self.next_deadline {
+            let wake_time = self.next_deadline;
+            self.next_deadline += self.frame_duration;
+            return Some(wake_time);
+        }
+
+        // Whole frames that were missed are dropped instead of being caught up in a burst.
+        let late_frames = ((now - self.next_deadline).as_secs_f64() / self.frame_duration.as_secs_f64()) as u64;
+        if late_frames > 0 {
+            self.dropped_frames += late_frames;
+            self.next_deadline = now + self.frame_duration;
+        } else {
+            self.next_deadline += self.frame_duration;
+        }
+
+        None
+    }
+
+    pub(crate) fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+}
+
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN_DURATION {
+        thread::sleep(deadline - now - SPIN_DURATION);
+    }
+
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_DURATION: Duration = Duration::from_nanos(16_666_667);
+
+    fn scheduler_at(start: Instant, is_vsynced: bool) -> FrameScheduler {
+        FrameScheduler {
+            frame_duration: FRAME_DURATION,
+            next_deadline: start + FRAME_DURATION,
+            last_frame_end: start,
+            is_vsynced,
+            dropped_frames: 0,
+        }
+    }
+
+    #[test]
+    fn frames_on_time_do_not_drift() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_at(start, false);
+
+        // Every frame takes 5 ms of work, then waits for its deadline.
+        let mut frame_start = start;
+        for _ in 0..600 {
+            frame_start = scheduler.advance(frame_start + Duration::from_millis(5)).unwrap();
+        }
+
+        assert_eq!(frame_start, start + FRAME_DURATION * 600);
+        assert_eq!(scheduler.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn slightly_late_frame_keeps_the_deadlines() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_at(start, false);
+
+        assert_eq!(scheduler.advance(start + FRAME_DURATION * 3 / 2), None);
+        assert_eq!(scheduler.next_deadline, start + FRAME_DURATION * 2);
+        assert_eq!(scheduler.dropped_frames(), 0);
+    }
+
+    #[test]
+    fn frame_two_periods_late_drops_the_missed_frames() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_at(start, false);
+
+        let now = start + FRAME_DURATION * 3 + FRAME_DURATION / 5;
+        assert_eq!(scheduler.advance(now), None);
+        assert_eq!(scheduler.dropped_frames(), 2);
+        // The next deadline restarts from the late frame, instead of catching up in a burst.
+        assert_eq!(scheduler.next_deadline, now + FRAME_DURATION);
+    }
+
+    #[test]
+    fn vsynced_frames_count_the_missed_refreshes() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_at(start, true);
+
+        // Time since the previous frame ended, and the refreshes missed in between.
+        for (elapsed, dropped_frames) in [(FRAME_DURATION, 0), (FRAME_DURATION * 2, 1), (FRAME_DURATION * 17 / 5, 3)] {
+            let now = scheduler.last_frame_end + elapsed;
+            assert_eq!(scheduler.advance(now), None);
+            assert_eq!(scheduler.dropped_frames(), dropped_frames);
+            scheduler.last_frame_end = now;
+        }
+    }
+
+    #[test]
+    fn vsynced_present_that_did_not_wait_is_paced() {
+        let start = Instant::now();
+        let mut scheduler = scheduler_at(start, true);
+
+        assert_eq!(scheduler.advance(start + FRAME_DURATION / 10), Some(start + FRAME_DURATION));
+        assert_eq!(scheduler.dropped_frames(), 0);
+    }
+}
@@ -1,86 +1,599 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
 use sdl2::audio::{AudioSpecDesired, AudioCallback, AudioDevice};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
-use sdl2::{pixels::Color, keyboard::Keycode, EventPump, render::Canvas};
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::video::{FullscreenType, WindowContext};
+use sdl2::keyboard::Mod;
+use sdl2::{pixels::Color, keyboard::Keycode, EventPump, GameControllerSubsystem, render::Canvas};
 
-use crate::display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::audio::{Buzzer, BuzzerSettings, SoundEvent, SoundRegisters, SAMPLE_RATE};
+use crate::crt::{self, CrtSettings};
+use crate::display::{Display, Palette, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::frontend::Frontend;
+use crate::keymap::Keymap;
+use crate::browser::MenuView;
+use crate::osd::{self, Osd};
 use crate::Event;
 
 
-const PIXEL_SCALE: u32 = 16;
+// The window size in screen pixels per CHIP-8 pixel, unless the [display] scale is set.
+const DEFAULT_PIXEL_SCALE: u32 = 16;
 
-pub(crate) struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32
-}
+const AUDIO_BUFFER_SAMPLES: u16 = 512;
+// Sound events are scheduled this far ahead of the samples being played, to cover the audio buffer.
+const AUDIO_LATENCY_SAMPLES: u32 = 2 * AUDIO_BUFFER_SAMPLES as u32;
+const WINDOW_TITLE: &str = "Chip 8";
+
+// Enough for two-player games, which split the keypad between two controllers.
+const MAX_CONTROLLERS: usize = 2;
+const VSYNC_REFRESH_RATES: std::ops::RangeInclusive<i32> = 59..=61;
+// How far a stick or trigger has to move before it counts as pressed, out of 32767.
+const AXIS_THRESHOLD: i16 = 16384;
 
-impl AudioCallback for SquareWave {
+// The frame cost shown on the overlay is averaged over a few frames, so it is readable.
+const FRAME_COST_SMOOTHING: f32 = 0.05;
+
+impl AudioCallback for Buzzer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.fill(out);
     }
 }
 
-pub(crate) fn init_sdl() -> (AudioDevice<SquareWave>, Canvas<sdl2::video::Window>, EventPump){
-    let sdl_context = sdl2::init().unwrap();
-    let video = sdl_context.video().unwrap();
-    let audio = sdl_context.audio().unwrap();
-    let event_pump = sdl_context.event_pump().unwrap();
+#[derive(Clone, Copy, PartialEq)]
+enum ScaleMode {
+    // Every emulated pixel covers the same whole number of window pixels.
+    Integer,
+    // The screen fills as much of the window as possible, keeping its aspect ratio.
+    AspectFit,
+}
+
+pub(crate) struct SdlFrontend {
+    audio_device: Option<AudioDevice<Buzzer>>,
+    canvas: Canvas<sdl2::video::Window>,
+    is_vsynced: bool,
+    event_pump: EventPump,
+
+    texture_creator: TextureCreator<WindowContext>,
+    texture: Texture,
+    texture_size: (usize, usize),
+    uploaded_frame: Option<(Vec<u8>, Palette)>,
+    scale_mode: ScaleMode,
+    use_crt: bool,
+    crt_settings: CrtSettings,
+
+    // The fill_rect renderer is kept around to compare frame costs against the texture renderer.
+    use_rect_renderer: bool,
+    texture_frame_cost_ms: Option<f32>,
+    rect_frame_cost_ms: Option<f32>,
+    status: String,
+
+    osd: Osd,
+    is_menu_open: bool,
+    show_counters: bool,
+    frames_per_second: f64,
+    instructions_per_second: f64,
+
+    keymap: Keymap,
+    // Names of the host keys and controller inputs currently held.
+    held_host_keys: HashSet<String>,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // The controller of each player, the slot decides its "pad1"/"pad2" prefix.
+    controllers: [Option<GameController>; MAX_CONTROLLERS],
+}
+
+// Fails when there is no usable video output. A missing audio output only disables the sound.
+pub(crate) fn init_sdl(crt_settings: CrtSettings, buzzer_settings: BuzzerSettings, keymap: Keymap, pixel_scale: Option<u32>) -> Result<SdlFrontend, String> {
+    let pixel_scale = pixel_scale.unwrap_or(DEFAULT_PIXEL_SCALE);
+    let sdl_context = sdl2::init()?;
+    let video = sdl_context.video()?;
+    let event_pump = sdl_context.event_pump()?;
 
     let window = video.window(
-        "Chip 8", 
-        DISPLAY_WIDTH as u32 * PIXEL_SCALE, 
-        DISPLAY_HEIGHT as u32 * PIXEL_SCALE
-    ).position_centered().build().unwrap();
+        WINDOW_TITLE, 
+        DISPLAY_WIDTH as u32 * pixel_scale, 
+        DISPLAY_HEIGHT as u32 * pixel_scale
+    ).position_centered().resizable().build().map_err(|error| error.to_string())?;
+
+    // Vsync only paces the emulation on displays that refresh at the CHIP-8 frame rate.
+    let refresh_rate = window.display_index()
+        .and_then(|display_index| video.current_display_mode(display_index))
+        .map(|display_mode| display_mode.refresh_rate)
+        .unwrap_or(0);
+    let is_vsynced = VSYNC_REFRESH_RATES.contains(&refresh_rate);
+
+    let canvas_builder = window.into_canvas();
+    let canvas_builder = if is_vsynced { canvas_builder.present_vsync() } else { canvas_builder };
+    let mut canvas = canvas_builder.build().map_err(|error| error.to_string())?;
+    let _ = canvas.window_mut().set_minimum_size(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32);
+
+    let texture_creator = canvas.texture_creator();
+    let texture = create_screen_texture(&texture_creator, DISPLAY_WIDTH, DISPLAY_HEIGHT);
+
+    let audio_device = match init_audio(&sdl_context, buzzer_settings) {
+        Ok(device) => Some(device),
+        Err(error) => {
+            eprintln!("Unable to open the audio output, continuing without sound: {}", error);
+            None
+        },
+    };
+
+    // Controllers that are already plugged in are reported as added devices by the event pump.
+    let controller_subsystem = match sdl_context.game_controller() {
+        Ok(subsystem) => Some(subsystem),
+        Err(error) => {
+            eprintln!("Unable to initialize game controllers, continuing without them: {}", error);
+            None
+        },
+    };
+
+    Ok(SdlFrontend {
+        audio_device,
+        canvas,
+        is_vsynced,
+        event_pump,
+
+        texture_creator,
+        texture,
+        texture_size: (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        uploaded_frame: None,
+        scale_mode: ScaleMode::Integer,
+        use_crt: false,
+        crt_settings,
+
+        use_rect_renderer: false,
+        texture_frame_cost_ms: None,
+        rect_frame_cost_ms: None,
+        status: String::new(),
+
+        osd: Osd::new(),
+        is_menu_open: false,
+        show_counters: false,
+        frames_per_second: 0.0,
+        instructions_per_second: 0.0,
 
-    let canvas = window.into_canvas().build().unwrap();
+        keymap,
+        held_host_keys: HashSet::new(),
+        controller_subsystem,
+        controllers: Default::default(),
+    })
+}
+
+fn init_audio(sdl_context: &sdl2::Sdl, buzzer_settings: BuzzerSettings) -> Result<AudioDevice<Buzzer>, String> {
+    let audio = sdl_context.audio()?;
 
     let desired_audio_spec = AudioSpecDesired {
-        freq: Some(44100),
+        freq: Some(SAMPLE_RATE as i32),
         channels: Some(1),
-        samples: None,
+        samples: Some(AUDIO_BUFFER_SAMPLES),
     };
 
     let device = audio.open_playback(None, &desired_audio_spec, | spec | {
-        SquareWave {
-            phase_inc: 440.0 / spec.freq as f32,
-            phase: 0.0,
-            volume: 0.25
+        Buzzer::new(spec.freq, buzzer_settings, AUDIO_LATENCY_SAMPLES)
+    })?;
+    buzzer_settings.check_frequency(device.spec().freq)?;
+
+    // The device keeps running, the buzzer is gated instead, so its envelope can play out.
+    device.resume();
+
+    Ok(device)
+}
+
+impl Frontend for SdlFrontend {
+    fn handle_event_loop(&mut self) -> Vec<Event> {
+        handle_event_loop(self)
+    }
+
+    fn update_display(&mut self, display: &Display, palette: &Palette) {
+        self.is_menu_open = false;
+        let frame_start = Instant::now();
+
+        let (output_width, output_height) = self.canvas.output_size().unwrap_or((1, 1));
+        let screen_rect = fit_screen_rect(output_width, output_height, display.width(), display.height(), self.scale_mode);
+
+        // Clear the letterbox bars around the screen.
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        if self.use_rect_renderer {
+            draw_with_rects(&mut self.canvas, display, palette, screen_rect);
+            add_frame_cost(&mut self.rect_frame_cost_ms, frame_start);
+        } else {
+            self.upload_frame(display, palette);
+            let _ = self.canvas.copy(&self.texture, None, screen_rect);
+            add_frame_cost(&mut self.texture_frame_cost_ms, frame_start);
+        }
+
+        if self.show_counters {
+            self.osd.counters = Some(self.format_counters());
+        }
+        self.osd.draw(&mut self.canvas);
+
+        self.canvas.present();
+    }
+
+    fn queue_sound_events(&mut self, events: &[SoundEvent], clocks_per_frame: u32, frame_duration: f64) {
+        if let Some(audio_device) = &mut self.audio_device {
+            let frame_samples = audio_device.spec().freq as f64 * frame_duration;
+            audio_device.lock().schedule_frame(events, clocks_per_frame, frame_samples);
+        }
+    }
+
+    fn set_muted(&mut self, is_muted: bool) {
+        if let Some(audio_device) = &mut self.audio_device {
+            audio_device.lock().set_muted(is_muted);
         }
-    }).unwrap();
+    }
+
+    fn set_sound_registers(&mut self, registers: SoundRegisters) {
+        if let Some(audio_device) = &mut self.audio_device {
+            audio_device.lock().set_registers(registers);
+        }
+    }
+
+    fn is_vsynced(&self) -> bool {
+        self.is_vsynced
+    }
+
+    fn show_menu(&mut self, menu: &MenuView) {
+        self.is_menu_open = true;
+
+        osd::draw_menu(&mut self.canvas, menu);
+        self.osd.draw(&mut self.canvas);
+        self.canvas.present();
+    }
+
+    fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+        self.update_title();
+    }
+
+    fn set_paused(&mut self, is_paused: bool) {
+        self.osd.is_paused = is_paused;
+    }
 
-    (device, canvas, event_pump)
+    fn show_message(&mut self, message: &str) {
+        self.osd.show_message(message);
+    }
+
+    fn set_counters(&mut self, frames_per_second: f64, instructions_per_second: f64) {
+        self.frames_per_second = frames_per_second;
+        self.instructions_per_second = instructions_per_second;
+    }
 }
 
-pub(crate) fn handle_event_loop(event_pump: &mut EventPump) -> Vec<Event> {
+impl SdlFrontend {
+    fn upload_frame(&mut self, display: &Display, palette: &Palette) {
+        // The emulated resolution can change at runtime, the texture has to follow it.
+        let texture_scale = if self.use_crt { self.crt_settings.scale.max(1) } else { 1 };
+        let texture_size = (display.width() * texture_scale, display.height() * texture_scale);
+        if self.texture_size != texture_size {
+            let new_texture = create_screen_texture(&self.texture_creator, texture_size.0, texture_size.1);
+            let old_texture = std::mem::replace(&mut self.texture, new_texture);
+            unsafe { old_texture.destroy(); }
+
+            self.texture_size = texture_size;
+            self.uploaded_frame = None;
+        }
+
+        if let Some((frame, frame_palette)) = &self.uploaded_frame {
+            if frame[..] == display.data[..] && frame_palette == palette {
+                return;
+            }
+        }
+
+        if self.use_crt {
+            let pixels = crt::render(display, palette, &self.crt_settings);
+            let line_len = texture_size.0 * 3;
+
+            let _ = self.texture.with_lock(None, |buffer, pitch| {
+                for (y_index, line) in pixels.chunks(line_len).enumerate() {
+                    let line_start = y_index * pitch;
+                    buffer[line_start..(line_start + line_len)].copy_from_slice(line);
+                }
+            });
+        } else {
+            let _ = self.texture.with_lock(None, |buffer, pitch| {
+                for (y_index, line) in display.data.chunks(display.width()).enumerate() {
+                    let line_start = y_index * pitch;
+                    for (x_index, intensity) in line.iter().enumerate() {
+                        let pixel_start = line_start + x_index * 3;
+                        buffer[pixel_start..(pixel_start + 3)].copy_from_slice(&palette.color_for(*intensity));
+                    }
+                }
+            });
+        }
+
+        self.uploaded_frame = Some((display.data.to_vec(), *palette));
+    }
+
+    fn format_counters(&self) -> String {
+        let (renderer, frame_cost_ms) = if self.use_rect_renderer {
+            ("FILL_RECT", self.rect_frame_cost_ms)
+        } else {
+            ("TEXTURE", self.texture_frame_cost_ms)
+        };
+
+        format!(
+            "{:.0} FPS  {:.0} IPS  {} {:.3} MS",
+            self.frames_per_second,
+            self.instructions_per_second,
+            renderer,
+            frame_cost_ms.unwrap_or(0.0)
+        )
+    }
+
+    fn update_title(&mut self) {
+        let title = format!("{} - {}", WINDOW_TITLE, self.status);
+        let _ = self.canvas.window_mut().set_title(&title);
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen_type = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+
+        let _ = window.set_fullscreen(fullscreen_type);
+    }
+}
+
+fn create_screen_texture(texture_creator: &TextureCreator<WindowContext>, width: usize, height: usize) -> Texture {
+    texture_creator.create_texture_streaming(
+        PixelFormatEnum::RGB24,
+        width as u32,
+        height as u32
+    ).unwrap()
+}
+
+fn fit_screen_rect(output_width: u32, output_height: u32, screen_width: usize, screen_height: usize, scale_mode: ScaleMode) -> Rect {
+    let (screen_width, screen_height) = (screen_width as u32, screen_height as u32);
+
+    let (width, height) = match scale_mode {
+        ScaleMode::Integer => {
+            let scale = (output_width / screen_width).min(output_height / screen_height).max(1);
+
+            (screen_width * scale, screen_height * scale)
+        },
+        ScaleMode::AspectFit => {
+            if output_width * screen_height > output_height * screen_width {
+                (output_height * screen_width / screen_height, output_height)
+            } else {
+                (output_width, output_width * screen_height / screen_width)
+            }
+        },
+    };
+
+    let x = (output_width as i32 - width as i32) / 2;
+    let y = (output_height as i32 - height as i32) / 2;
+
+    Rect::new(x, y, width.max(1), height.max(1))
+}
+
+fn add_frame_cost(average_cost_ms: &mut Option<f32>, frame_start: Instant) {
+    let cost_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
+
+    *average_cost_ms = Some(match average_cost_ms {
+        Some(average) => *average + (cost_ms - *average) * FRAME_COST_SMOOTHING,
+        None => cost_ms,
+    });
+}
+
+impl SdlFrontend {
+    fn press_host_key(&mut self, name: String, result: &mut Vec<Event>) {
+        let key_nums = self.keymap.key_nums(&name);
+        if self.held_host_keys.insert(name) {
+            for key_num in key_nums {
+                result.push(Event::KeyPressed(key_num))
+            }
+        }
+    }
+
+    fn release_host_key(&mut self, name: &str, result: &mut Vec<Event>) {
+        if !self.held_host_keys.remove(name) {
+            return;
+        }
+
+        // A CHIP-8 key stays pressed while any of its host keys is held.
+        for key_num in self.keymap.key_nums(name) {
+            let is_still_held = self.held_host_keys.iter()
+                .any(|held_name| self.keymap.key_nums(held_name).contains(&key_num));
+            if !is_still_held {
+                result.push(Event::KeyReleased(key_num))
+            }
+        }
+    }
+
+    // `device_index` is the joystick device index, not an instance id.
+    fn add_controller(&mut self, device_index: u32) {
+        let Some(subsystem) = &self.controller_subsystem else { return };
+        let Some(free_slot) = self.controllers.iter().position(Option::is_none) else { return };
+
+        match subsystem.open(device_index) {
+            Ok(controller) => {
+                // A controller can be reported twice, when it is plugged in during startup.
+                if self.pad_name(controller.instance_id()).is_none() {
+                    self.controllers[free_slot] = Some(controller);
+                }
+            },
+            Err(error) => eprintln!("Unable to open game controller {}: {}", device_index, error),
+        }
+    }
+
+    fn remove_controller(&mut self, instance_id: u32, result: &mut Vec<Event>) {
+        let Some(pad_name) = self.pad_name(instance_id) else { return };
+
+        // Nothing on the controller can be released anymore.
+        let prefix = format!("{} ", pad_name);
+        let held_inputs = self.held_host_keys.iter()
+            .filter(|name| name.starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in held_inputs {
+            self.release_host_key(&name, result);
+        }
+
+        for controller in &mut self.controllers {
+            if matches!(controller, Some(open_controller) if open_controller.instance_id() == instance_id) {
+                *controller = None;
+            }
+        }
+    }
+
+    fn pad_name(&self, instance_id: u32) -> Option<String> {
+        self.controllers.iter()
+            .position(|controller| matches!(controller, Some(open_controller) if open_controller.instance_id() == instance_id))
+            .map(|slot| format!("pad{}", slot + 1))
+    }
+
+    // Sticks and triggers act as buttons once pushed past the threshold, one per direction.
+    fn move_axis(&mut self, pad_name: &str, axis: Axis, value: i16, result: &mut Vec<Event>) {
+        let (negative_name, positive_name) = match axis {
+            Axis::LeftX => (Some("left stick left"), "left stick right"),
+            Axis::LeftY => (Some("left stick up"), "left stick down"),
+            Axis::RightX => (Some("right stick left"), "right stick right"),
+            Axis::RightY => (Some("right stick up"), "right stick down"),
+            Axis::TriggerLeft => (None, "left trigger"),
+            Axis::TriggerRight => (None, "right trigger"),
+        };
+
+        let directions = [
+            (negative_name, value < -AXIS_THRESHOLD),
+            (Some(positive_name), value > AXIS_THRESHOLD),
+        ];
+        for (name, is_pushed) in directions {
+            let Some(name) = name else { continue };
+            let name = format!("{} {}", pad_name, name);
+            if is_pushed {
+                self.press_host_key(name, result);
+            } else {
+                self.release_host_key(&name, result);
+            }
+        }
+    }
+}
+
+fn handle_event_loop(frontend: &mut SdlFrontend) -> Vec<Event> {
     let mut result = Vec::new();
 
-    for event in event_pump.poll_iter() {
+    let sdl_events = frontend.event_pump.poll_iter().collect::<Vec<_>>();
+    for event in sdl_events {
         match event {
             sdl2::event::Event::Quit {..} => {
                 result.push(Event::Quit)
             },
-            sdl2::event::Event::KeyDown { keycode: Some(code), ..} => {
+            sdl2::event::Event::DropFile { filename, .. } => {
+                result.push(Event::LoadRom(filename))
+            },
+            sdl2::event::Event::KeyDown { keycode: Some(code), .. } if frontend.is_menu_open => {
+                match code {
+                    Keycode::Escape => result.push(Event::Quit),
+                    Keycode::Up => result.push(Event::MenuUp),
+                    Keycode::Down => result.push(Event::MenuDown),
+                    Keycode::Return | Keycode::KpEnter => result.push(Event::MenuSelect),
+                    Keycode::Backspace => result.push(Event::MenuBackspace),
+                    _ => { }
+                }
+            },
+            sdl2::event::Event::TextInput { text, .. } if frontend.is_menu_open => {
+                result.push(Event::MenuText(text))
+            },
+            sdl2::event::Event::ControllerButtonDown { button, .. } if frontend.is_menu_open => {
+                match button {
+                    Button::DPadUp => result.push(Event::MenuUp),
+                    Button::DPadDown => result.push(Event::MenuDown),
+                    Button::A | Button::Start => result.push(Event::MenuSelect),
+                    Button::B => result.push(Event::MenuBackspace),
+                    _ => { }
+                }
+            },
+            sdl2::event::Event::KeyDown { keycode: Some(code), keymod, ..} => {
                 if code == Keycode::Escape {
                     result.push(Event::Quit)
                 }
 
-                if let Some(key_num) = try_keycode_into_key_num(code) {
-                    result.push(Event::KeyPressed(key_num))
+                if let Some(control_event) = try_keycode_into_control_event(code, keymod) {
+                    result.push(control_event);
+                    continue;
+                }
+
+                if code == Keycode::F3 {
+                    frontend.show_counters = !frontend.show_counters;
+                    if !frontend.show_counters {
+                        frontend.osd.counters = None;
+                    }
+                }
+
+                if code == Keycode::F4 {
+                    frontend.use_rect_renderer = !frontend.use_rect_renderer;
                 }
+
+                if code == Keycode::F6 {
+                    frontend.use_crt = !frontend.use_crt;
+                }
+
+                if code == Keycode::F7 {
+                    frontend.scale_mode = match frontend.scale_mode {
+                        ScaleMode::Integer => ScaleMode::AspectFit,
+                        ScaleMode::AspectFit => ScaleMode::Integer,
+                    };
+                }
+
+                if code == Keycode::F11 {
+                    frontend.toggle_fullscreen();
+                }
+
+                if code == Keycode::F8 {
+                    result.push(Event::ToggleMute)
+                }
+
+                if code == Keycode::F10 {
+                    result.push(Event::ToggleRecording)
+                }
+
+                if code == Keycode::F12 {
+                    result.push(Event::Screenshot)
+                }
+
+                frontend.press_host_key(code.name(), &mut result);
             },
             sdl2::event::Event::KeyUp { keycode: Some(code), ..} => {
-                if let Some(key_num) = try_keycode_into_key_num(code) {
-                    result.push(Event::KeyReleased(key_num))
+                if code == Keycode::Tab {
+                    result.push(Event::StopFastForward);
+                    continue;
+                }
+
+                frontend.release_host_key(&code.name(), &mut result);
+            },
+            sdl2::event::Event::ControllerDeviceAdded { which, .. } => {
+                frontend.add_controller(which);
+            },
+            sdl2::event::Event::ControllerDeviceRemoved { which, .. } => {
+                frontend.remove_controller(which, &mut result);
+            },
+            sdl2::event::Event::ControllerButtonDown { which, button, .. } => {
+                if let Some(pad_name) = frontend.pad_name(which) {
+                    frontend.press_host_key(format!("{} {}", pad_name, button.string()), &mut result);
+                }
+            },
+            sdl2::event::Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(pad_name) = frontend.pad_name(which) {
+                    frontend.release_host_key(&format!("{} {}", pad_name, button.string()), &mut result);
+                }
+            },
+            sdl2::event::Event::ControllerAxisMotion { which, axis, value, .. } => {
+                if let Some(pad_name) = frontend.pad_name(which) {
+                    frontend.move_axis(&pad_name, axis, value, &mut result);
                 }
             },
             _ => { }
@@ -90,43 +603,42 @@ pub(crate) fn handle_event_loop(event_pump: &mut EventPump) -> Vec<Event> {
     result
 }
 
-fn try_keycode_into_key_num(keycode: Keycode) -> Option<u8> {
+// Emulator controls. They take precedence over the keypad bindings.
+fn try_keycode_into_control_event(keycode: Keycode, keymod: Mod) -> Option<Event> {
+    let is_shift_held = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+
     match keycode {
-        Keycode::Num1 => { Some(0x1) },
-        Keycode::Num2 => { Some(0x2) },
-        Keycode::Num3 => { Some(0x3) },
-        Keycode::Num4 => { Some(0xC) },
-
-        Keycode::Q => { Some(0x4) },
-        Keycode::W => { Some(0x5) },
-        Keycode::E => { Some(0x6) },
-        Keycode::R => { Some(0xD) },
-
-        Keycode::A => { Some(0x7) },
-        Keycode::S => { Some(0x8) },
-        Keycode::D => { Some(0x9) },
-        Keycode::F => { Some(0xE) },
-
-        Keycode::Z => { Some(0xA) },
-        Keycode::X => { Some(0x0) },
-        Keycode::C => { Some(0xB) },
-        Keycode::V => { Some(0xF) },
+        Keycode::F1 | Keycode::Pause => { Some(Event::TogglePause) },
+        Keycode::F2 => { Some(Event::AdvanceFrame) },
+        Keycode::F5 if is_shift_held => { Some(Event::SaveState) },
+        Keycode::F6 if is_shift_held => { Some(Event::LoadState) },
+        Keycode::F5 => { Some(Event::StepInstruction) },
+        Keycode::F9 if is_shift_held => { Some(Event::HardReset) },
+        Keycode::F9 => { Some(Event::SoftReset) },
+        Keycode::PageUp if is_shift_held => { Some(Event::IncreaseInstructionsPerFrame) },
+        Keycode::PageDown if is_shift_held => { Some(Event::DecreaseInstructionsPerFrame) },
+        Keycode::PageUp => { Some(Event::SpeedUp) },
+        Keycode::PageDown => { Some(Event::SpeedDown) },
+        Keycode::Tab => { Some(Event::StartFastForward) },
         _ => None
     }
 }
 
-pub(crate) fn update_display(canvas: &mut Canvas<sdl2::video::Window>, display: &Display) {
+fn draw_with_rects(canvas: &mut Canvas<sdl2::video::Window>, display: &Display, palette: &Palette, screen_rect: Rect) {
+    let pixel_width = (screen_rect.width() / display.width() as u32).max(1);
+    let pixel_height = (screen_rect.height() / display.height() as u32).max(1);
+
     let mut data_index = 0;
-    for y_index in 0..(DISPLAY_HEIGHT as u32) {
-        for x_index in 0..(DISPLAY_WIDTH as u32) {
-            let color = display.data[data_index];
-            canvas.set_draw_color(Color::RGB(color, color, color));
+    for y_index in 0..(display.height() as u32) {
+        for x_index in 0..(display.width() as u32) {
+            let [r, g, b] = palette.color_for(display.data[data_index]);
+            canvas.set_draw_color(Color::RGB(r, g, b));
 
-            let _ = canvas.fill_rect(Rect::new((x_index * PIXEL_SCALE) as i32, (y_index * PIXEL_SCALE) as i32, PIXEL_SCALE, PIXEL_SCALE));
+            let x = screen_rect.x() + (x_index * pixel_width) as i32;
+            let y = screen_rect.y() + (y_index * pixel_height) as i32;
+            let _ = canvas.fill_rect(Rect::new(x, y, pixel_width, pixel_height));
 
             data_index += 1;
         }
     }
-    
-    canvas.present();
 }
\ No newline at end of file
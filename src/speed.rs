@@ -0,0 +1,55 @@
+use std::str::FromStr;
+
+use crate::config::Config;
+
+const DEFAULT_FAST_FORWARD_SPEED: f64 = 4.0;
+const MAX_FAST_FORWARD_SPEED: f64 = 10.0;
+
+// How fast the emulation runs while the fast-forward key is held.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum FastForward {
+    // A multiple of the normal speed, from 2x to 10x.
+    Speed(f64),
+    // As many frames as fit in the time of a real frame. The audio is muted.
+    Uncapped,
+}
+
+impl FastForward {
+    pub(crate) fn from_config(config: &Config) -> Result<Self, String> {
+        Ok(config.get_parsed("speed", "fast_forward")?.unwrap_or(FastForward::Speed(DEFAULT_FAST_FORWARD_SPEED)))
+    }
+}
+
+impl FromStr for FastForward {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name == "uncapped" {
+            return Ok(FastForward::Uncapped);
+        }
+
+        match name.trim_end_matches('x').parse::<f64>() {
+            Ok(speed) if (2.0..=MAX_FAST_FORWARD_SPEED).contains(&speed) => Ok(FastForward::Speed(speed)),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_forward_speeds_are_parsed() {
+        assert!("4".parse::<FastForward>() == Ok(FastForward::Speed(4.0)));
+        assert!("4x".parse::<FastForward>() == Ok(FastForward::Speed(4.0)));
+        assert!("uncapped".parse::<FastForward>() == Ok(FastForward::Uncapped));
+    }
+
+    #[test]
+    fn fast_forward_speeds_out_of_range_are_rejected() {
+        for text in ["1.5", "11", "nan", "inf", "", "fast"] {
+            assert!(text.parse::<FastForward>().is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// A frontend for terminals without a graphical display. Every character cell shows two
+// pixels using the upper half block, with the top pixel as foreground color and the
+// bottom pixel as background color.
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::audio::{SoundEvent, SoundRegisters};
+use crate::browser::MenuView;
+use crate::display::{Display, Palette};
+use crate::frontend::Frontend;
+use crate::keymap::Keymap;
+use crate::Event;
+
+// Terminals only report key presses, never releases, so a key is held until it stops
+// auto-repeating. The first press waits longer, since auto-repeat only starts after a delay.
+const FIRST_KEY_HOLD_TIME: Duration = Duration::from_millis(600);
+const KEY_HOLD_TIME: Duration = Duration::from_millis(150);
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+const ESCAPE: u8 = 0x1B;
+const CTRL_C: u8 = 0x03;
+const TAB: u8 = b'\t';
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+// Menu lines, leaving room for the title, the search filter and the details.
+const MENU_VISIBLE_ITEMS: usize = 12;
+const BELL: &str = "\x07";
+const F1_SEQUENCE: &[u8] = b"OP";
+const F2_SEQUENCE: &[u8] = b"OQ";
+const F5_SEQUENCE: &[u8] = b"[15~";
+const SHIFT_F5_SEQUENCE: &[u8] = b"[15;2~";
+const SHIFT_F6_SEQUENCE: &[u8] = b"[17;2~";
+const F8_SEQUENCE: &[u8] = b"[19~";
+const F9_SEQUENCE: &[u8] = b"[20~";
+const SHIFT_F9_SEQUENCE: &[u8] = b"[20;2~";
+const PAGE_UP_SEQUENCE: &[u8] = b"[5~";
+const PAGE_DOWN_SEQUENCE: &[u8] = b"[6~";
+const SHIFT_PAGE_UP_SEQUENCE: &[u8] = b"[5;2~";
+const SHIFT_PAGE_DOWN_SEQUENCE: &[u8] = b"[6;2~";
+const F10_SEQUENCE: &[u8] = b"[21~";
+const F12_SEQUENCE: &[u8] = b"[24~";
+
+pub(crate) struct TerminalFrontend {
+    original_mode: libc::termios,
+    keymap: Keymap,
+    key_release_times: [Option<Instant>; 16],
+    fast_forward_release_time: Option<Instant>,
+    last_frame: String,
+    is_menu_open: bool,
+    status: String,
+    // The last message and when it was shown, it follows the status for a while.
+    message: Option<(String, Instant)>,
+    sound_end_time: Instant,
+    is_muted: bool,
+}
+
+pub(crate) fn init_terminal(keymap: Keymap) -> Result<TerminalFrontend, String> {
+    let original_mode = enable_raw_mode().map_err(|error| format!("Unable to switch the terminal to raw mode: {}", error))?;
+
+    // Switch to the alternate screen and hide the cursor.
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b[?1049h\x1b[?25l\x1b[2J");
+    let _ = stdout.flush();
+
+    Ok(TerminalFrontend {
+        original_mode,
+        keymap,
+        key_release_times: [None; 16],
+        fast_forward_release_time: None,
+        last_frame: String::new(),
+        is_menu_open: false,
+        status: String::new(),
+        message: None,
+        sound_end_time: Instant::now(),
+        is_muted: false,
+    })
+}
+
+fn enable_raw_mode() -> io::Result<libc::termios> {
+    unsafe {
+        let mut mode: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut mode) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let original_mode = mode;
+
+        // Non-blocking reads: return whatever input is available, even if there is none.
+        libc::cfmakeraw(&mut mode);
+        mode.c_cc[libc::VMIN] = 0;
+        mode.c_cc[libc::VTIME] = 0;
+
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &mode) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(original_mode)
+    }
+}
+
+impl TerminalFrontend {
+    // Writes the frame, unless the terminal already shows it.
+    fn present(&mut self, frame: String) {
+        if frame == self.last_frame {
+            return;
+        }
+
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+
+        self.last_frame = frame;
+    }
+
+    fn press_host_key(&mut self, key_name: &str, now: Instant, result: &mut Vec<Event>) {
+        for key_num in self.keymap.key_nums(key_name) {
+            let release_time = &mut self.key_release_times[key_num as usize];
+            if release_time.is_none() {
+                result.push(Event::KeyPressed(key_num));
+                *release_time = Some(now + FIRST_KEY_HOLD_TIME);
+            } else {
+                *release_time = Some(now + KEY_HOLD_TIME);
+            }
+        }
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn handle_event_loop(&mut self) -> Vec<Event> {
+        let mut result = Vec::new();
+        let now = Instant::now();
+
+        let mut input = [0u8; 64];
+        let input_len = io::stdin().lock().read(&mut input).unwrap_or(0);
+
+        let mut input_index = 0;
+        while input_index < input_len {
+            let byte = input[input_index];
+            input_index += 1;
+
+            match byte {
+                CTRL_C => {
+                    result.push(Event::Quit)
+                },
+                // Fast-forward lasts while Tab is held, which is emulated like the keypad keys.
+                TAB => {
+                    if self.fast_forward_release_time.is_none() {
+                        result.push(Event::StartFastForward);
+                        self.fast_forward_release_time = Some(now + FIRST_KEY_HOLD_TIME);
+                    } else {
+                        self.fast_forward_release_time = Some(now + KEY_HOLD_TIME);
+                    }
+                },
+                ESCAPE => {
+                    // A lone escape is the Escape key, otherwise it starts an escape sequence
+                    // (arrows, function keys...).
+                    if input_index == input_len {
+                        result.push(Event::Quit);
+                    } else if input[input_index] == b'[' || input[input_index] == b'O' {
+                        let sequence_start = input_index;
+
+                        input_index += 1;
+                        while input_index < input_len && !(0x40..=0x7E).contains(&input[input_index]) {
+                            input_index += 1;
+                        }
+                        input_index = (input_index + 1).min(input_len);
+
+                        match &input[sequence_start..input_index] {
+                            F1_SEQUENCE => result.push(Event::TogglePause),
+                            F2_SEQUENCE => result.push(Event::AdvanceFrame),
+                            F5_SEQUENCE => result.push(Event::StepInstruction),
+                            SHIFT_F5_SEQUENCE => result.push(Event::SaveState),
+                            SHIFT_F6_SEQUENCE => result.push(Event::LoadState),
+                            F8_SEQUENCE => result.push(Event::ToggleMute),
+                            F9_SEQUENCE => result.push(Event::SoftReset),
+                            SHIFT_F9_SEQUENCE => result.push(Event::HardReset),
+                            PAGE_UP_SEQUENCE => result.push(Event::SpeedUp),
+                            PAGE_DOWN_SEQUENCE => result.push(Event::SpeedDown),
+                            SHIFT_PAGE_UP_SEQUENCE => result.push(Event::IncreaseInstructionsPerFrame),
+                            SHIFT_PAGE_DOWN_SEQUENCE => result.push(Event::DecreaseInstructionsPerFrame),
+                            F10_SEQUENCE => result.push(Event::ToggleRecording),
+                            F12_SEQUENCE => result.push(Event::Screenshot),
+                            sequence => {
+                                match arrow_key_name(sequence) {
+                                    Some("up") if self.is_menu_open => result.push(Event::MenuUp),
+                                    Some("down") if self.is_menu_open => result.push(Event::MenuDown),
+                                    Some(key_name) if !self.is_menu_open => self.press_host_key(key_name, now, &mut result),
+                                    _ => { }
+                                }
+                            },
+                        }
+                    }
+                },
+                _ if self.is_menu_open => {
+                    match byte {
+                        b'\r' => result.push(Event::MenuSelect),
+                        BACKSPACE | DELETE => result.push(Event::MenuBackspace),
+                        _ if byte.is_ascii_graphic() || byte == b' ' => result.push(Event::MenuText((byte as char).to_string())),
+                        _ => { }
+                    }
+                },
+                _ => {
+                    if let Some(key_name) = byte_key_name(byte) {
+                        self.press_host_key(&key_name, now, &mut result);
+                    }
+                },
+            }
+        }
+
+        if matches!(self.fast_forward_release_time, Some(time) if time <= now) {
+            self.fast_forward_release_time = None;
+            result.push(Event::StopFastForward);
+        }
+
+        for (key_num, release_time) in self.key_release_times.iter_mut().enumerate() {
+            if matches!(release_time, Some(time) if *time <= now) {
+                *release_time = None;
+                result.push(Event::KeyReleased(key_num as u8));
+            }
+        }
+
+        result
+    }
+
+    fn update_display(&mut self, display: &Display, palette: &Palette) {
+        self.is_menu_open = false;
+
+        let mut frame = String::from("\x1b[H");
+
+        let width = display.width();
+        let height = display.height();
+
+        for y_index in (0..height).step_by(2) {
+            let mut last_colors = None;
+
+            for x_index in 0..width {
+                let top = palette.color_for(display.data[y_index * width + x_index]);
+                let bottom = match y_index + 1 {
+                    bottom_index if bottom_index < height => palette.color_for(display.data[bottom_index * width + x_index]),
+                    _ => palette.background,
+                };
+
+                if last_colors != Some((top, bottom)) {
+                    frame.push_str(&format!(
+                        "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                    ));
+                    last_colors = Some((top, bottom));
+                }
+
+                frame.push(UPPER_HALF_BLOCK);
+            }
+
+            frame.push_str("\x1b[0m\r\n");
+        }
+
+        // The status goes under the screen, clearing whatever was left below it.
+        frame.push_str(&self.status);
+        if let Some((message, shown_at)) = &self.message {
+            if shown_at.elapsed() < MESSAGE_DURATION {
+                frame.push_str(" - ");
+                frame.push_str(message);
+            }
+        }
+        frame.push_str("\x1b[J");
+
+        self.present(frame);
+    }
+
+    // The bell can't be held, so it rings once whenever a sound starts.
+    fn queue_sound_events(&mut self, events: &[SoundEvent], _clocks_per_frame: u32, frame_duration: f64) {
+        let now = Instant::now();
+
+        for event in events {
+            if event.duration > 0 && now >= self.sound_end_time && !self.is_muted {
+                let mut stdout = io::stdout();
+                let _ = write!(stdout, "{}", BELL);
+                let _ = stdout.flush();
+            }
+
+            self.sound_end_time = now + Duration::from_secs_f64(event.duration as f64 * frame_duration);
+        }
+    }
+
+    fn set_muted(&mut self, is_muted: bool) {
+        self.is_muted = is_muted;
+    }
+
+    // The terminal bell has a fixed sound.
+    fn set_sound_registers(&mut self, _registers: SoundRegisters) { }
+
+    fn show_menu(&mut self, menu: &MenuView) {
+        self.is_menu_open = true;
+
+        // Every line clears what is left of the previous frame on it.
+        let mut frame = String::from("\x1b[H\x1b[0m");
+        frame.push_str(&format!("{}\x1b[K\r\n", menu.title));
+        frame.push_str(&format!("Search: {}_\x1b[K\r\n\x1b[K\r\n", menu.filter));
+
+        let visible_range = menu.visible_range(MENU_VISIBLE_ITEMS);
+        for item_index in visible_range.clone() {
+            if Some(item_index) == menu.selected {
+                // Reverse video for the selected item.
+                frame.push_str(&format!("\x1b[7m> {}\x1b[0m\x1b[K\r\n", menu.items[item_index]));
+            } else {
+                frame.push_str(&format!("  {}\x1b[K\r\n", menu.items[item_index]));
+            }
+        }
+        if menu.items.is_empty() {
+            frame.push_str("  No ROM found.\x1b[K\r\n");
+        }
+
+        frame.push_str("\x1b[K\r\n");
+        for detail in &menu.details {
+            frame.push_str(&format!("{}\x1b[K\r\n", detail));
+        }
+        frame.push_str("\x1b[J");
+
+        self.present(frame);
+    }
+
+    fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+    }
+
+    fn show_message(&mut self, message: &str) {
+        self.message = Some((message.to_string(), Instant::now()));
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original_mode);
+        }
+    }
+}
+
+// Names of the keys sent as a single byte, matching the names used by the SDL frontend.
+fn byte_key_name(byte: u8) -> Option<String> {
+    match byte {
+        b' ' => { Some("space".to_string()) },
+        b'\r' => { Some("return".to_string()) },
+        _ if byte.is_ascii_graphic() => { Some((byte.to_ascii_lowercase() as char).to_string()) },
+        _ => None
+    }
+}
+
+// Arrow keys are sent as "ESC [ A" or, in application cursor mode, "ESC O A".
+fn arrow_key_name(sequence: &[u8]) -> Option<&'static str> {
+    match sequence {
+        b"[A" | b"OA" => { Some("up") },
+        b"[B" | b"OB" => { Some("down") },
+        b"[C" | b"OC" => { Some("right") },
+        b"[D" | b"OD" => { Some("left") },
+        _ => None
+    }
+}
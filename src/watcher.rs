@@ -0,0 +1,128 @@
+// Notices when the ROM file changes on disk, by polling its modification time and size.
+// Polling needs no platform specific API, and a ROM is only checked a couple of times a second.
+
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub(crate) struct RomWatcher {
+    path: String,
+    // Modification time and size of the loaded version of the file.
+    loaded_stamp: Option<(SystemTime, u64)>,
+    // A newer version, waiting to stop changing.
+    pending_stamp: Option<(SystemTime, u64)>,
+    last_poll: Instant,
+}
+
+impl RomWatcher {
+    pub(crate) fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            loaded_stamp: file_stamp(path),
+            pending_stamp: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // True once the file has changed and then stayed the same for a whole poll interval,
+    // so a file that is still being written isn't loaded half way.
+    pub(crate) fn has_changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        self.poll()
+    }
+
+    fn poll(&mut self) -> bool {
+        // The file may be replaced instead of rewritten, wait for it to come back.
+        let Some(stamp) = file_stamp(&self.path) else { return false };
+
+        if Some(stamp) == self.loaded_stamp {
+            self.pending_stamp = None;
+            false
+        } else if Some(stamp) == self.pending_stamp {
+            self.loaded_stamp = Some(stamp);
+            self.pending_stamp = None;
+            true
+        } else {
+            self.pending_stamp = Some(stamp);
+            false
+        }
+    }
+}
+
+fn file_stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempRom {
+        path: String,
+    }
+
+    impl TempRom {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("chip8-{}-{}.ch8", name, std::process::id()));
+            let rom = Self { path: path.to_string_lossy().into_owned() };
+            rom.write(&[0x12, 0x00]);
+            rom
+        }
+
+        // Every write in the tests changes the size, the modification time may be too coarse
+        // to tell writes apart.
+        fn write(&self, rom: &[u8]) {
+            fs::write(&self.path, rom).unwrap();
+        }
+    }
+
+    impl Drop for TempRom {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn change_is_reported_once_the_file_is_stable() {
+        let rom = TempRom::new("stable");
+        let mut watcher = RomWatcher::new(&rom.path);
+        assert!(!watcher.poll());
+
+        rom.write(&[0x12, 0x00, 0x00]);
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn file_still_being_written_is_not_reported() {
+        let rom = TempRom::new("writing");
+        let mut watcher = RomWatcher::new(&rom.path);
+
+        rom.write(&[0x12, 0x00, 0x00]);
+        assert!(!watcher.poll());
+        rom.write(&[0x12, 0x00, 0x00, 0x00]);
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+    }
+
+    #[test]
+    fn deleted_and_recreated_file_is_reported() {
+        let rom = TempRom::new("recreated");
+        let mut watcher = RomWatcher::new(&rom.path);
+
+        fs::remove_file(&rom.path).unwrap();
+        assert!(!watcher.poll());
+        assert!(!watcher.poll());
+
+        rom.write(&[0x12, 0x00, 0x00]);
+        assert!(!watcher.poll());
+        assert!(watcher.poll());
+    }
+}
@@ -0,0 +1,51 @@
+// A YUV4MPEG2 writer. Frames are stored as full resolution 4:4:4 planes, so no
+// chroma information is lost.
+
+use std::io::{self, Write};
+
+use crate::display::{Display, Palette, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+pub(crate) struct Y4mWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub(crate) fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444", DISPLAY_WIDTH, DISPLAY_HEIGHT)?;
+
+        Ok(Self { out })
+    }
+
+    pub(crate) fn write_frame(&mut self, display: &Display, palette: &Palette) -> io::Result<()> {
+        let pixel_count = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+        let mut planes = vec![0u8; pixel_count * 3];
+
+        for (pixel_index, intensity) in display.data.iter().enumerate() {
+            let (y, u, v) = rgb_to_yuv(palette.color_for(*intensity));
+
+            planes[pixel_index] = y;
+            planes[pixel_count + pixel_index] = u;
+            planes[2 * pixel_count + pixel_index] = v;
+        }
+
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&planes)
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+}
+
+// BT.601, limited range.
+fn rgb_to_yuv([r, g, b]: [u8; 3]) -> (u8, u8, u8) {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+
+    (y as u8, u as u8, v as u8)
+}